@@ -5,7 +5,11 @@ $topaz = File.join(project_root, 'target', 'release', 'topaz')
 
 def run_dir(dir)
 	Dir.each_child(dir) do |filename|
-		run_file File.join(dir, filename)
+		path = File.join(dir, filename)
+		# subdirectories hold modules imported by the specs
+		next if File.directory? path
+
+		run_file path
 	end
 end
 
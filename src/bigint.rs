@@ -0,0 +1,642 @@
+use std::cmp::Ordering;
+use std::fmt::{self, Display, Formatter};
+use std::ops::{Add, BitAnd, BitOr, BitXor, Mul, Neg, Shl, Shr, Sub};
+use std::str::FromStr;
+
+/// arbitrary precision integer, stored as a sign and a magnitude of
+/// little-endian base 2^32 limbs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BigInt {
+    negative: bool,
+    mag: Vec<u32>, // no trailing zero limbs, zero is empty and never negative
+}
+
+/// removes leading zero limbs from a magnitude
+fn trim(mut mag: Vec<u32>) -> Vec<u32> {
+    while mag.last() == Some(&0) {
+        mag.pop();
+    }
+    mag
+}
+
+fn cmp_mag(a: &[u32], b: &[u32]) -> Ordering {
+    if a.len() != b.len() {
+        return a.len().cmp(&b.len());
+    }
+
+    for (x, y) in a.iter().rev().zip(b.iter().rev()) {
+        if x != y {
+            return x.cmp(y);
+        }
+    }
+
+    Ordering::Equal
+}
+
+fn add_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let (long, short) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    let mut result = Vec::with_capacity(long.len() + 1);
+    let mut carry = 0u64;
+
+    for (i, &x) in long.iter().enumerate() {
+        let sum = x as u64 + *short.get(i).unwrap_or(&0) as u64 + carry;
+        result.push(sum as u32);
+        carry = sum >> 32;
+    }
+
+    if carry > 0 {
+        result.push(carry as u32);
+    }
+
+    result
+}
+
+/// subtracts b from a, where |a| >= |b|
+fn sub_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+
+    for (i, &x) in a.iter().enumerate() {
+        let mut diff = x as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+        if diff < 0 {
+            diff += 1 << 32;
+            borrow = 1;
+        } else {
+            borrow = 0;
+        }
+        result.push(diff as u32);
+    }
+
+    trim(result)
+}
+
+fn mul_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    if a.is_empty() || b.is_empty() {
+        return Vec::new();
+    }
+
+    let mut result = vec![0u32; a.len() + b.len()];
+    for (i, &x) in a.iter().enumerate() {
+        let mut carry = 0u64;
+        for (j, &y) in b.iter().enumerate() {
+            let cur = result[i + j] as u64 + x as u64 * y as u64 + carry;
+            result[i + j] = cur as u32;
+            carry = cur >> 32;
+        }
+        result[i + b.len()] = carry as u32;
+    }
+
+    trim(result)
+}
+
+/// multiplies a magnitude by a small factor and adds a small value to it
+fn mul_add_small(a: &[u32], factor: u32, addend: u32) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len() + 1);
+    let mut carry = addend as u64;
+
+    for &x in a {
+        let cur = x as u64 * factor as u64 + carry;
+        result.push(cur as u32);
+        carry = cur >> 32;
+    }
+
+    if carry > 0 {
+        result.push(carry as u32);
+    }
+
+    trim(result)
+}
+
+fn divrem_small(a: &[u32], divisor: u32) -> (Vec<u32>, u32) {
+    let mut quotient = vec![0u32; a.len()];
+    let mut rem = 0u64;
+
+    for i in (0..a.len()).rev() {
+        let cur = (rem << 32) | a[i] as u64;
+        quotient[i] = (cur / divisor as u64) as u32;
+        rem = cur % divisor as u64;
+    }
+
+    (trim(quotient), rem as u32)
+}
+
+/// long division of magnitudes, b must not be zero
+fn divrem_mag(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    if cmp_mag(a, b) == Ordering::Less {
+        return (Vec::new(), a.to_vec());
+    }
+
+    if b.len() == 1 {
+        let (q, r) = divrem_small(a, b[0]);
+        return (q, trim(vec![r]));
+    }
+
+    // shift-and-subtract, one bit at a time
+    let mut quotient = vec![0u32; a.len()];
+    let mut rem: Vec<u32> = Vec::new();
+    for i in (0..a.len() * 32).rev() {
+        let bit = (a[i / 32] >> (i % 32)) & 1;
+        rem = mul_add_small(&rem, 2, bit);
+
+        if cmp_mag(&rem, b) != Ordering::Less {
+            rem = sub_mag(&rem, b);
+            quotient[i / 32] |= 1 << (i % 32);
+        }
+    }
+
+    (trim(quotient), rem)
+}
+
+impl BigInt {
+    fn new(negative: bool, mag: Vec<u32>) -> BigInt {
+        let mag = trim(mag);
+        BigInt {
+            negative: negative && !mag.is_empty(),
+            mag,
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.mag.is_empty()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    /// returns the value as an i64 if it fits in one
+    pub fn to_i64(&self) -> Option<i64> {
+        if self.mag.len() > 2 {
+            return None;
+        }
+
+        let mut m = 0u64;
+        for &limb in self.mag.iter().rev() {
+            m = (m << 32) | limb as u64;
+        }
+
+        if self.negative {
+            if m <= 1 << 63 {
+                Some((m as i64).wrapping_neg())
+            } else {
+                None
+            }
+        } else {
+            i64::try_from(m).ok()
+        }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        let mut result = 0f64;
+        for &limb in self.mag.iter().rev() {
+            result = result * 4294967296f64 + limb as f64;
+        }
+
+        if self.negative {
+            -result
+        } else {
+            result
+        }
+    }
+
+    /// converts the integral part of a finite float
+    pub fn from_f64(x: f64) -> Option<BigInt> {
+        if !x.is_finite() {
+            return None;
+        }
+
+        let x = x.trunc();
+        if x.abs() < 9.223372036854776e18 {
+            return Some(BigInt::from(x as i64));
+        }
+
+        // large floats are always integral: mantissa * 2^exponent
+        let bits = x.to_bits();
+        let exponent = ((bits >> 52) & 0x7ff) as usize - 1075;
+        let mantissa = (bits & ((1 << 52) - 1)) | (1 << 52);
+        let result = &BigInt::from(mantissa as i64) << exponent;
+
+        Some(if x < 0f64 { -&result } else { result })
+    }
+
+    /// parses digits in the given radix, ignoring `_` separators
+    pub fn from_str_radix(s: &str, radix: u32) -> Option<BigInt> {
+        let (negative, digits) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s),
+        };
+
+        if digits.is_empty() {
+            return None;
+        }
+
+        let mut mag = Vec::new();
+        for c in digits.chars().filter(|&c| c != '_') {
+            let digit = c.to_digit(radix)?;
+            mag = mul_add_small(&mag, radix, digit);
+        }
+
+        Some(BigInt::new(negative, mag))
+    }
+
+    /// quotient and remainder, truncating towards zero
+    pub fn checked_div_rem(&self, other: &BigInt) -> Option<(BigInt, BigInt)> {
+        if other.is_zero() {
+            return None;
+        }
+
+        let (q, r) = divrem_mag(&self.mag, &other.mag);
+        Some((
+            BigInt::new(self.negative != other.negative, q),
+            BigInt::new(self.negative, r),
+        ))
+    }
+
+    /// quotient rounded towards negative infinity
+    pub fn checked_div_floor(&self, other: &BigInt) -> Option<BigInt> {
+        let (q, r) = self.checked_div_rem(other)?;
+        if !r.is_zero() && self.negative != other.negative {
+            Some(&q - &BigInt::from(1))
+        } else {
+            Some(q)
+        }
+    }
+
+    /// remainder with the sign of the dividend
+    pub fn checked_rem(&self, other: &BigInt) -> Option<BigInt> {
+        self.checked_div_rem(other).map(|(_, r)| r)
+    }
+
+    /// remainder with the sign of the divisor, matching `checked_div_floor`
+    pub fn checked_rem_floor(&self, other: &BigInt) -> Option<BigInt> {
+        let r = self.checked_rem(other)?;
+        if !r.is_zero() && self.negative != other.negative {
+            Some(&r + other)
+        } else {
+            Some(r)
+        }
+    }
+
+    /// number of bits in the magnitude, 0 for zero
+    pub fn bits(&self) -> u64 {
+        match self.mag.last() {
+            Some(top) => self.mag.len() as u64 * 32 - top.leading_zeros() as u64,
+            None => 0,
+        }
+    }
+
+    /// raises to a power by repeated squaring
+    pub fn pow(&self, mut exp: u64) -> BigInt {
+        let mut base = self.clone();
+        let mut result = BigInt::from(1);
+        while exp > 0 {
+            if exp & 1 == 1 {
+                result = &result * &base;
+            }
+            exp >>= 1;
+            if exp > 0 {
+                base = &base * &base;
+            }
+        }
+        result
+    }
+
+    /// two's complement representation using the given number of limbs
+    fn to_twos_complement(&self, len: usize) -> Vec<u32> {
+        let mut limbs = self.mag.clone();
+        limbs.resize(len, 0);
+
+        if self.negative {
+            let mut carry = 1u64;
+            for limb in limbs.iter_mut() {
+                let cur = (!*limb) as u64 + carry;
+                *limb = cur as u32;
+                carry = cur >> 32;
+            }
+        }
+
+        limbs
+    }
+
+    fn from_twos_complement(mut limbs: Vec<u32>) -> BigInt {
+        let negative = limbs.last().is_some_and(|&top| top & 0x8000_0000 != 0);
+        if negative {
+            let mut carry = 1u64;
+            for limb in limbs.iter_mut() {
+                let cur = (!*limb) as u64 + carry;
+                *limb = cur as u32;
+                carry = cur >> 32;
+            }
+        }
+
+        BigInt::new(negative, limbs)
+    }
+
+    fn bitwise(&self, other: &BigInt, op: fn(u32, u32) -> u32) -> BigInt {
+        let len = self.mag.len().max(other.mag.len()) + 1;
+        let a = self.to_twos_complement(len);
+        let b = other.to_twos_complement(len);
+        let limbs = a.iter().zip(b.iter()).map(|(&x, &y)| op(x, y)).collect();
+        BigInt::from_twos_complement(limbs)
+    }
+}
+
+impl From<i64> for BigInt {
+    fn from(n: i64) -> Self {
+        let m = n.unsigned_abs();
+        BigInt::new(n < 0, vec![m as u32, (m >> 32) as u32])
+    }
+}
+
+impl FromStr for BigInt {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        BigInt::from_str_radix(s, 10).ok_or(())
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_mag(&self.mag, &other.mag),
+            (true, true) => cmp_mag(&other.mag, &self.mag),
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Neg for &BigInt {
+    type Output = BigInt;
+
+    fn neg(self) -> BigInt {
+        BigInt::new(!self.negative, self.mag.clone())
+    }
+}
+
+impl Add for &BigInt {
+    type Output = BigInt;
+
+    fn add(self, other: &BigInt) -> BigInt {
+        if self.negative == other.negative {
+            return BigInt::new(self.negative, add_mag(&self.mag, &other.mag));
+        }
+
+        match cmp_mag(&self.mag, &other.mag) {
+            Ordering::Less => BigInt::new(other.negative, sub_mag(&other.mag, &self.mag)),
+            _ => BigInt::new(self.negative, sub_mag(&self.mag, &other.mag)),
+        }
+    }
+}
+
+impl Sub for &BigInt {
+    type Output = BigInt;
+
+    fn sub(self, other: &BigInt) -> BigInt {
+        self + &-other
+    }
+}
+
+impl Mul for &BigInt {
+    type Output = BigInt;
+
+    fn mul(self, other: &BigInt) -> BigInt {
+        BigInt::new(
+            self.negative != other.negative,
+            mul_mag(&self.mag, &other.mag),
+        )
+    }
+}
+
+impl Shl<usize> for &BigInt {
+    type Output = BigInt;
+
+    fn shl(self, bits: usize) -> BigInt {
+        let mut mag = vec![0u32; bits / 32];
+        let shift = bits % 32;
+        let mut carry = 0u32;
+
+        for &limb in &self.mag {
+            if shift == 0 {
+                mag.push(limb);
+            } else {
+                mag.push((limb << shift) | carry);
+                carry = limb >> (32 - shift);
+            }
+        }
+
+        if carry > 0 {
+            mag.push(carry);
+        }
+
+        BigInt::new(self.negative, mag)
+    }
+}
+
+impl Shr<usize> for &BigInt {
+    type Output = BigInt;
+
+    /// arithmetic shift, rounding towards negative infinity
+    fn shr(self, bits: usize) -> BigInt {
+        if self.negative {
+            // floor(-a / 2^n) == -((a - 1) >> n) - 1
+            let one = BigInt::from(1);
+            let shifted = &(&(-self) - &one) >> bits;
+            return &(-&shifted) - &one;
+        }
+
+        let skip = bits / 32;
+        if skip >= self.mag.len() {
+            return BigInt::from(0);
+        }
+
+        let shift = bits % 32;
+        let limbs = &self.mag[skip..];
+        let mag = (0..limbs.len())
+            .map(|i| {
+                if shift == 0 {
+                    limbs[i]
+                } else {
+                    let high = limbs.get(i + 1).map_or(0, |&next| next << (32 - shift));
+                    (limbs[i] >> shift) | high
+                }
+            })
+            .collect();
+
+        BigInt::new(false, mag)
+    }
+}
+
+impl BitAnd for &BigInt {
+    type Output = BigInt;
+
+    fn bitand(self, other: &BigInt) -> BigInt {
+        self.bitwise(other, |a, b| a & b)
+    }
+}
+
+impl BitOr for &BigInt {
+    type Output = BigInt;
+
+    fn bitor(self, other: &BigInt) -> BigInt {
+        self.bitwise(other, |a, b| a | b)
+    }
+}
+
+impl BitXor for &BigInt {
+    type Output = BigInt;
+
+    fn bitxor(self, other: &BigInt) -> BigInt {
+        self.bitwise(other, |a, b| a ^ b)
+    }
+}
+
+impl Display for BigInt {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+
+        // peel off 9 decimal digits at a time
+        let mut chunks = Vec::new();
+        let mut mag = self.mag.clone();
+        while !mag.is_empty() {
+            let (q, r) = divrem_small(&mag, 1_000_000_000);
+            chunks.push(r);
+            mag = q;
+        }
+
+        if self.negative {
+            write!(f, "-")?;
+        }
+
+        write!(f, "{}", chunks.last().unwrap())?;
+        for chunk in chunks.iter().rev().skip(1) {
+            write!(f, "{:09}", chunk)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn big(s: &str) -> BigInt {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_and_displays_round_trip() {
+        let s = "-123456789012345678901234567890";
+        assert_eq!(big(s).to_string(), s);
+        assert_eq!(big("0").to_string(), "0");
+        assert_eq!(big("-0").to_string(), "0");
+    }
+
+    #[test]
+    fn converts_to_and_from_i64() {
+        assert_eq!(BigInt::from(i64::MIN).to_i64(), Some(i64::MIN));
+        assert_eq!(BigInt::from(i64::MAX).to_i64(), Some(i64::MAX));
+        assert_eq!(big("9223372036854775808").to_i64(), None);
+        assert_eq!(big("-9223372036854775809").to_i64(), None);
+    }
+
+    #[test]
+    fn adds_and_subtracts_across_signs() {
+        let a = big("18446744073709551616");
+        let b = big("-1");
+        assert_eq!((&a + &b).to_string(), "18446744073709551615");
+        assert_eq!((&b - &a).to_string(), "-18446744073709551617");
+        assert_eq!((&a - &a).to_string(), "0");
+    }
+
+    #[test]
+    fn multiplies() {
+        let a = big("123456789012345678901234567890");
+        let b = big("-987654321098765432109876543210");
+        assert_eq!(
+            (&a * &b).to_string(),
+            "-121932631137021795226185032733622923332237463801111263526900"
+        );
+    }
+
+    #[test]
+    fn divides_with_truncation_and_floor() {
+        let a = big("-100000000000000000000000000001");
+        let b = big("7");
+        let (q, r) = a.checked_div_rem(&b).unwrap();
+        assert_eq!(q.to_string(), "-14285714285714285714285714285");
+        assert_eq!(r.to_string(), "-6");
+        assert_eq!(
+            a.checked_div_floor(&b).unwrap().to_string(),
+            "-14285714285714285714285714286"
+        );
+        assert_eq!(a.checked_rem_floor(&b).unwrap().to_string(), "1");
+        assert!(a.checked_div_rem(&BigInt::from(0)).is_none());
+    }
+
+    #[test]
+    fn divides_by_multi_limb_divisor() {
+        let a = big("340282366920938463463374607431768211456");
+        let b = big("18446744073709551617");
+        let (q, r) = a.checked_div_rem(&b).unwrap();
+        assert_eq!(q.to_string(), "18446744073709551615");
+        assert_eq!(r.to_string(), "1");
+    }
+
+    #[test]
+    fn shifts() {
+        let one = BigInt::from(1);
+        assert_eq!((&one << 100).to_string(), "1267650600228229401496703205376");
+        assert_eq!((&(&one << 100) >> 99).to_string(), "2");
+        assert_eq!((&BigInt::from(-5) >> 1).to_string(), "-3");
+    }
+
+    #[test]
+    fn bitwise_uses_twos_complement() {
+        let a = big("-18446744073709551616");
+        let b = big("18446744073709551615");
+        assert_eq!((&a & &b).to_string(), "0");
+        assert_eq!((&a | &b).to_string(), "-1");
+        assert_eq!((&BigInt::from(-1) ^ &BigInt::from(5)).to_string(), "-6");
+    }
+
+    #[test]
+    fn orders_by_sign_then_magnitude() {
+        assert!(big("-100000000000000000000") < big("-1"));
+        assert!(big("100000000000000000000") > big("99999999999999999999"));
+    }
+
+    #[test]
+    fn converts_large_floats() {
+        assert_eq!(
+            BigInt::from_f64(1e20).unwrap().to_string(),
+            "100000000000000000000"
+        );
+        assert_eq!(BigInt::from_f64(-2.5).unwrap().to_string(), "-2");
+        assert!(BigInt::from_f64(f64::NAN).is_none());
+    }
+
+    #[test]
+    fn raises_to_powers() {
+        assert_eq!(
+            BigInt::from(2).pow(100).to_string(),
+            "1267650600228229401496703205376"
+        );
+        assert_eq!(
+            BigInt::from(-3).pow(41).to_string(),
+            "-36472996377170786403"
+        );
+        assert_eq!(BigInt::from(7).pow(0).to_string(), "1");
+        assert_eq!(BigInt::from(2).pow(100).bits(), 101);
+    }
+}
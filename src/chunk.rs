@@ -80,6 +80,39 @@ impl Chunk {
             Opcode::Jump => self.jump_instruction("Jump", 1, offset),
             Opcode::Loop => self.jump_instruction("Loop", -1, offset),
             Opcode::Call => self.byte_instruction("Call", offset),
+            Opcode::IntDivide => self.simple_instruction("IntDivide", offset),
+            Opcode::BitwiseXor => self.simple_instruction("BitwiseXor", offset),
+            Opcode::ShiftLeft => self.simple_instruction("ShiftLeft", offset),
+            Opcode::ShiftRight => self.simple_instruction("ShiftRight", offset),
+            Opcode::Concat => self.byte_instruction("Concat", offset),
+            Opcode::Closure => self.closure_instruction(offset),
+            Opcode::GetUpvalue => self.byte_instruction("GetUpvalue", offset),
+            Opcode::SetUpvalue => self.byte_instruction("SetUpvalue", offset),
+            Opcode::PopLocals => self.byte_instruction("PopLocals", offset),
+            Opcode::BuildList => self.byte_instruction("BuildList", offset),
+            Opcode::BuildMap => self.byte_instruction("BuildMap", offset),
+            Opcode::GetIndex => self.simple_instruction("GetIndex", offset),
+            Opcode::SetIndex => self.simple_instruction("SetIndex", offset),
+            Opcode::IsList => self.byte_instruction("IsList", offset),
+            Opcode::IsMap => self.simple_instruction("IsMap", offset),
+            Opcode::HasKey => self.constant_instruction("HasKey", offset),
+            Opcode::InRange => self.byte_instruction("InRange", offset),
+            Opcode::JumpTable => self.jump_table_instruction(offset),
+            Opcode::MatchError => self.simple_instruction("MatchError", offset),
+            Opcode::ExpectList => self.byte_instruction("ExpectList", offset),
+            Opcode::ExpectKey => self.constant_instruction("ExpectKey", offset),
+            Opcode::Duplicate => self.byte_instruction("Duplicate", offset),
+            Opcode::GetField => self.constant_instruction("GetField", offset),
+            Opcode::SetField => self.constant_instruction("SetField", offset),
+            Opcode::PushHandler => self.handler_instruction(offset),
+            Opcode::PopHandler => self.simple_instruction("PopHandler", offset),
+            Opcode::Throw => self.simple_instruction("Throw", offset),
+            Opcode::Invoke => self.invoke_instruction(offset),
+            Opcode::IsErr => self.simple_instruction("IsErr", offset),
+            Opcode::UnwrapOk => self.simple_instruction("UnwrapOk", offset),
+            Opcode::Import => self.constant_instruction("Import", offset),
+            Opcode::Power => self.simple_instruction("Power", offset),
+            Opcode::EndFinally => self.simple_instruction("EndFinally", offset),
             _ => {
                 println!("Unknown opcode: {}", instruction);
                 offset + 1
@@ -105,6 +138,71 @@ impl Chunk {
         offset + 2
     }
 
+    /// a method call names the method, then gives the number of arguments
+    fn invoke_instruction(&self, offset: usize) -> usize {
+        let constant = self.code[offset + 1] as usize;
+        let num_args = self.code[offset + 2];
+        println!(
+            "Invoke {} {} ({} args)",
+            constant, self.constants[constant], num_args
+        );
+        offset + 3
+    }
+
+    /// a handler jumps to its catch, and to its finally block when returning, if it has one
+    fn handler_instruction(&self, offset: usize) -> usize {
+        let catch = ((self.code[offset + 1] as usize) << 8) | self.code[offset + 2] as usize;
+        let finally = ((self.code[offset + 3] as usize) << 8) | self.code[offset + 4] as usize;
+        print!("PushHandler {} -> {}", offset, offset + 3 + catch);
+        if finally != 0 {
+            print!(", finally -> {}", offset + 5 + finally);
+        }
+        println!();
+        offset + 5
+    }
+
+    /// a closure is followed by a (is_local, index) pair for each captured variable
+    fn closure_instruction(&self, offset: usize) -> usize {
+        let constant = self.code[offset + 1] as usize;
+        println!("Closure {} {}", constant, self.constants[constant]);
+
+        let mut offset = offset + 2;
+        if let Value::Function(f) = &self.constants[constant] {
+            for _ in 0..f.upvalue_count {
+                let kind = if self.code[offset] == 1 {
+                    "local"
+                } else {
+                    "upvalue"
+                };
+                println!("{:04}    |   {} {}", offset, kind, self.code[offset + 1]);
+                offset += 2;
+            }
+        }
+
+        offset
+    }
+
+    /// a jump table holds an offset for each value from its minimum, then a default
+    fn jump_table_instruction(&self, offset: usize) -> usize {
+        let constant = self.code[offset + 1] as usize;
+        let count = self.code[offset + 2] as usize;
+        println!(
+            "JumpTable {} {} {}",
+            constant, self.constants[constant], count
+        );
+
+        let table = offset + 3;
+        let table_end = table + 2 * (count + 1);
+        for entry in 0..=count {
+            let at = table + 2 * entry;
+            let jump = ((self.code[at] as usize) << 8) | self.code[at + 1] as usize;
+            println!("{:04}    |   {} -> {}", at, entry, table_end + jump);
+        }
+
+        table_end
+    }
+
+    #[allow(clippy::unnecessary_cast)]
     fn jump_instruction(&self, name: &str, sign: i32, offset: usize) -> usize {
         let mut jump = (self.code[offset + 1] as u16) << 8;
         jump |= self.code[offset + 2] as u16;
@@ -1,4 +1,21 @@
 use crate::chunk::Chunk;
+use crate::module::Module;
+use crate::value::Value;
+use crate::vm::Vm;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// signature of functions implemented in rust, errors become runtime errors
+pub type NativeFn = fn(vm: &mut Vm, args: &[Value]) -> Result<Value, String>;
+
+/// a variable captured by a closure. It points at the variable's stack slot
+/// until the slot goes out of scope, then holds the value itself
+#[derive(Debug, Clone)]
+pub enum Upvalue {
+    Open(usize),
+    Closed(Value),
+}
 
 #[derive(Debug, Clone)]
 pub enum FunctionType {
@@ -12,7 +29,12 @@ pub struct Function {
     pub chunk: Chunk,
     pub name: String,
     pub native: bool,
+    pub native_fn: Option<NativeFn>,
+    pub doc: Option<String>, // from `##` comments above the definition
+    pub upvalue_count: usize,
+    pub upvalues: Vec<Rc<RefCell<Upvalue>>>, // filled in when the closure is created
     pub function_type: FunctionType,
+    pub module: Option<Rc<Module>>, // whose globals the function uses, set with the upvalues
 }
 
 impl Function {
@@ -22,7 +44,27 @@ impl Function {
             chunk: Chunk::new(),
             name,
             native: false,
+            native_fn: None,
+            doc: None,
+            upvalue_count: 0,
+            upvalues: Vec::new(),
             function_type,
+            module: None,
+        }
+    }
+
+    pub fn native(name: &str, native_fn: NativeFn) -> Function {
+        Function {
+            num_params: 0,
+            chunk: Chunk::new(),
+            name: String::from(name),
+            native: true,
+            native_fn: Some(native_fn),
+            doc: None,
+            upvalue_count: 0,
+            upvalues: Vec::new(),
+            function_type: FunctionType::Fn,
+            module: None,
         }
     }
 }
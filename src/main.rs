@@ -1,15 +1,24 @@
+mod bigint;
 mod chunk;
 mod function;
+mod manifest;
+mod method;
+mod module;
+mod native;
 mod opcode;
 mod operator;
+mod package;
 mod parse_rule;
 mod parser;
 mod precedence;
 mod scanner;
+mod stdlib;
 mod token;
 mod value;
 mod vm;
 
+use manifest::Manifest;
+use package::LOCK_FILE;
 use parser::Parser;
 use scanner::Scanner;
 use vm::Vm;
@@ -17,10 +26,26 @@ use vm::Vm;
 use std::{
     env, fs,
     io::{stdin, stdout, Write},
+    path::Path,
 };
 
-fn repl() {
+/// a vm searching the directories listed in TOPAZ_PATH for imported
+/// modules, with its random numbers seeded by `--seed`
+fn new_vm(seed: Option<u64>) -> Vm {
     let mut vm = Vm::new();
+    if let Some(paths) = env::var_os("TOPAZ_PATH") {
+        for dir in env::split_paths(&paths) {
+            vm.add_search_path(dir);
+        }
+    }
+    if let Some(seed) = seed {
+        vm.seed_random(seed);
+    }
+    vm
+}
+
+fn repl(seed: Option<u64>) {
+    let mut vm = new_vm(seed);
     let mut line_num = 1;
     loop {
         print!("> ");
@@ -51,11 +76,59 @@ fn repl() {
     }
 }
 
-fn run_file(fname: &str) {
-    let source =
-        fs::read_to_string(fname).unwrap_or_else(|_| panic!("Unable to open file {}", fname));
+fn run_file(fname: &str, seed: Option<u64>) {
+    let mut vm = new_vm(seed);
+    run_script(&mut vm, Path::new(fname));
+}
 
-    let mut vm = Vm::new();
+/// runs the project around the current directory from its entry file, with
+/// its dependencies importable by name. `--locked` refuses to run when the
+/// lockfile doesn't match the dependencies
+fn run_project(locked: bool, seed: Option<u64>) {
+    let cwd = env::current_dir().unwrap_or_default();
+    let root = match package::find_root(&cwd) {
+        Some(root) => root,
+        None => return println!("Cannot find topaz.toml in {} or above", cwd.display()),
+    };
+
+    let manifest = match Manifest::load(&root) {
+        Ok(manifest) => manifest,
+        Err(msg) => return println!("{}", msg),
+    };
+    let packages = match package::resolve(&root, &manifest) {
+        Ok(packages) => packages,
+        Err(msg) => return println!("{}", msg),
+    };
+
+    let lock = package::lockfile(&packages);
+    let lock_path = root.join(LOCK_FILE);
+    if fs::read_to_string(&lock_path).ok().as_ref() != Some(&lock) {
+        if locked {
+            return println!("{} is out of date", lock_path.display());
+        }
+        if let Err(e) = fs::write(&lock_path, lock) {
+            return println!("Cannot write {}: {}", lock_path.display(), e);
+        }
+    }
+    if let Err(msg) = package::install(&root, &packages) {
+        return println!("{}", msg);
+    }
+
+    let mut vm = new_vm(seed);
+    for package in packages {
+        vm.add_package(&package.name, package.entry);
+    }
+    run_script(&mut vm, &root.join(&manifest.entry));
+}
+
+fn run_script(vm: &mut Vm, path: &Path) {
+    let fname = path.display();
+    let source = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(e) => return println!("Cannot read {}: {}", fname, e),
+    };
+
+    vm.set_script_path(path);
 
     let res = Parser::new(source).compile();
     match res {
@@ -67,11 +140,43 @@ fn run_file(fname: &str) {
     }
 }
 
+/// takes `--seed n` out of the arguments
+fn take_seed(args: &mut Vec<String>) -> Result<Option<u64>, String> {
+    let i = match args.iter().position(|arg| arg == "--seed") {
+        Some(i) => i,
+        None => return Ok(None),
+    };
+    if i + 1 == args.len() {
+        return Err(String::from("--seed expects a non-negative integer"));
+    }
+
+    let seed = args.remove(i + 1);
+    args.remove(i);
+    match seed.parse::<u64>() {
+        Ok(n) => Ok(Some(n)),
+        Err(_) => Err(format!(
+            "--seed expects a non-negative integer but got '{}'",
+            seed
+        )),
+    }
+}
+
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    match args.len() {
-        1 => repl(),
-        2 => run_file(&args[1]),
-        _ => println!("Usage: topaz [path]"),
+    let mut args: Vec<String> = env::args().collect();
+    let seed = match take_seed(&mut args) {
+        Ok(seed) => seed,
+        Err(msg) => return println!("{}", msg),
+    };
+
+    // a script named `run` in the current directory is run rather than the project
+    let subcommand = !Path::new("run").is_file();
+    match args.get(1).map(String::as_str) {
+        None => repl(seed),
+        Some("run") if subcommand && args.len() == 2 => run_project(false, seed),
+        Some("run") if subcommand && args.len() == 3 && args[2] == "--locked" => {
+            run_project(true, seed)
+        }
+        Some(fname) if args.len() == 2 => run_file(fname, seed),
+        _ => println!("Usage: topaz [--seed n] [path] | topaz run [--locked] [--seed n]"),
     }
 }
@@ -0,0 +1,275 @@
+use crate::parser::is_identifier;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const MANIFEST_FILE: &str = "topaz.toml";
+
+/// a project's topaz.toml, naming the project, the file `topaz run` starts
+/// from and the packages it depends on
+///
+/// ```toml
+/// [package]
+/// name = "app"
+/// version = "0.1.0"
+/// entry = "src/main.tz"
+///
+/// [dependencies]
+/// shapes = { path = "../shapes" }
+/// colors = { archive = "vendor/colors-1.0.0.tar" }
+/// ```
+#[derive(Debug)]
+pub struct Manifest {
+    pub name: String,
+    pub version: String,
+    pub entry: PathBuf, // relative to the manifest, main.tz when not given
+    pub dependencies: Vec<Dependency>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dependency {
+    pub name: String,
+    pub source: Source,
+}
+
+/// where a dependency comes from, relative to the manifest declaring it.
+/// Both work offline
+#[derive(Debug, Clone, PartialEq)]
+pub enum Source {
+    Path(PathBuf),
+    Archive(PathBuf), // an uncompressed tar file
+}
+
+/// a value in the subset of toml manifests use
+enum Item {
+    String(String),
+    Table(BTreeMap<String, String>),
+}
+
+impl Manifest {
+    /// reads the manifest in a project directory
+    pub fn load(dir: &Path) -> Result<Manifest, String> {
+        let path = dir.join(MANIFEST_FILE);
+        let text =
+            fs::read_to_string(&path).map_err(|_| format!("Cannot read {}", path.display()))?;
+        Manifest::parse(&text).map_err(|msg| format!("{}: {}", path.display(), msg))
+    }
+
+    pub fn parse(text: &str) -> Result<Manifest, String> {
+        let mut tables = parse_tables(text)?;
+
+        let mut package = tables.remove("package").ok_or("Missing [package] table")?;
+        let mut field = |key: &str| match package.remove(key) {
+            Some(Item::String(value)) => Ok(Some(value)),
+            Some(Item::Table(_)) => Err(format!("package.{} must be a string", key)),
+            None => Ok(None),
+        };
+        let name = field("name")?.ok_or("Missing package.name")?;
+        let version = field("version")?.ok_or("Missing package.version")?;
+        let entry = PathBuf::from(field("entry")?.unwrap_or_else(|| String::from("main.tz")));
+
+        let mut dependencies = Vec::new();
+        for (name, item) in tables.remove("dependencies").unwrap_or_default() {
+            // the name is imported by and becomes a directory, so it can't be a path
+            if !is_identifier(&name) {
+                return Err(format!("Dependency name '{}' is not an identifier", name));
+            }
+
+            let source = match item {
+                // `name = "path"` is short for `name = { path = "path" }`
+                Item::String(path) => Source::Path(PathBuf::from(path)),
+                Item::Table(mut table) => match (table.remove("path"), table.remove("archive")) {
+                    (Some(path), None) => Source::Path(PathBuf::from(path)),
+                    (None, Some(archive)) => Source::Archive(PathBuf::from(archive)),
+                    _ => {
+                        let msg = format!("Dependency '{}' needs one of 'path' or 'archive'", name);
+                        return Err(msg);
+                    }
+                },
+            };
+            dependencies.push(Dependency { name, source });
+        }
+
+        if let Some(table) = tables.keys().next() {
+            return Err(format!("Unknown table [{}]", table));
+        }
+
+        Ok(Manifest {
+            name,
+            version,
+            entry,
+            dependencies,
+        })
+    }
+}
+
+/// splits a manifest into its `[table]`s of `key = value` lines
+fn parse_tables(text: &str) -> Result<BTreeMap<String, BTreeMap<String, Item>>, String> {
+    let mut tables: BTreeMap<String, BTreeMap<String, Item>> = BTreeMap::new();
+    let mut table = None;
+
+    for (i, line) in text.lines().enumerate() {
+        let error = |msg: &str| format!("line {}: {}", i + 1, msg);
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix('[') {
+            let name = name
+                .strip_suffix(']')
+                .ok_or_else(|| error("Expect ']' after table name"))?;
+            let name = String::from(name.trim());
+            if tables.contains_key(&name) {
+                return Err(error(&format!("Table [{}] is defined twice", name)));
+            }
+            tables.insert(name.clone(), BTreeMap::new());
+            table = Some(name);
+            continue;
+        }
+
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| error("Expect '=' after key"))?;
+        let key = parse_key(key).map_err(|m| error(&m))?;
+        let value = value.trim();
+        let item = if let Some(entries) = value.strip_prefix('{') {
+            let entries = entries
+                .strip_suffix('}')
+                .ok_or_else(|| error("Expect '}' after table"))?;
+            let mut inline = BTreeMap::new();
+            for entry in entries.split(',').filter(|e| !e.trim().is_empty()) {
+                let (k, v) = entry
+                    .split_once('=')
+                    .ok_or_else(|| error("Expect '=' after key"))?;
+                let k = parse_key(k).map_err(|m| error(&m))?;
+                inline.insert(k, parse_string(v.trim()).map_err(|m| error(&m))?);
+            }
+            Item::Table(inline)
+        } else {
+            Item::String(parse_string(value).map_err(|m| error(&m))?)
+        };
+
+        let table = table
+            .as_ref()
+            .ok_or_else(|| error("Expect a [table] before keys"))?;
+        let entries = tables.get_mut(table).unwrap();
+        if entries.insert(key.clone(), item).is_some() {
+            return Err(error(&format!("Key '{}' is defined twice", key)));
+        }
+    }
+
+    Ok(tables)
+}
+
+/// drops a `#` comment that isn't inside a string
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => (),
+        }
+    }
+    line
+}
+
+/// keys are bare or quoted like strings, the quotes aren't part of the key
+fn parse_key(key: &str) -> Result<String, String> {
+    let key = key.trim();
+    if key.starts_with('"') {
+        parse_string(key)
+    } else {
+        Ok(String::from(key))
+    }
+}
+
+fn parse_string(value: &str) -> Result<String, String> {
+    value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .filter(|v| !v.contains('"'))
+        .map(String::from)
+        .ok_or_else(|| format!("Expect a quoted string but got {}", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_package_and_dependencies() {
+        let manifest = Manifest::parse(
+            r#"
+            # an app
+            [package]
+            name = "app"
+            version = "0.1.0"
+            entry = "src/main.tz"
+
+            [dependencies]
+            shapes = { path = "../shapes" } # next to the app
+            colors = { archive = "vendor/colors.tar" }
+            util = "libs/util"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(manifest.name, "app");
+        assert_eq!(manifest.version, "0.1.0");
+        assert_eq!(manifest.entry, PathBuf::from("src/main.tz"));
+        assert_eq!(
+            manifest.dependencies,
+            vec![
+                Dependency {
+                    name: String::from("colors"),
+                    source: Source::Archive(PathBuf::from("vendor/colors.tar")),
+                },
+                Dependency {
+                    name: String::from("shapes"),
+                    source: Source::Path(PathBuf::from("../shapes")),
+                },
+                Dependency {
+                    name: String::from("util"),
+                    source: Source::Path(PathBuf::from("libs/util")),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn entry_defaults_to_main() {
+        let manifest = Manifest::parse("[package]\nname = \"a\"\nversion = \"1\"").unwrap();
+        assert_eq!(manifest.entry, PathBuf::from("main.tz"));
+        assert!(manifest.dependencies.is_empty());
+    }
+
+    #[test]
+    fn reports_errors_with_lines() {
+        let err = Manifest::parse("[package]\nname \"a\"").unwrap_err();
+        assert_eq!(err, "line 2: Expect '=' after key");
+
+        let err = Manifest::parse("[package]\nname = \"a\"").unwrap_err();
+        assert_eq!(err, "Missing package.version");
+    }
+
+    #[test]
+    fn rejects_dependency_names_that_are_paths() {
+        let manifest = |dep: &str| {
+            let text = format!(
+                "[package]\nname = \"a\"\nversion = \"1\"\n[dependencies]\n{}",
+                dep
+            );
+            Manifest::parse(&text)
+        };
+
+        let err = manifest("\"../../victim\" = { archive = \"v.tar\" }").unwrap_err();
+        assert_eq!(err, "Dependency name '../../victim' is not an identifier");
+        assert!(manifest("../victim = \"v\"").is_err());
+
+        let deps = manifest("\"shapes\" = \"../shapes\"").unwrap().dependencies;
+        assert_eq!(deps[0].name, "shapes");
+    }
+}
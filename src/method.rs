@@ -0,0 +1,69 @@
+use crate::native::arity;
+use crate::stdlib::{fs, string};
+use crate::value::Value;
+use crate::vm::Vm;
+
+/// calls a built-in method of a value, like `result.unwrap()`. Methods of
+/// maps are their fields and don't come through here
+pub fn call_method(
+    _vm: &mut Vm,
+    receiver: &Value,
+    name: &str,
+    args: &[Value],
+) -> Result<Value, String> {
+    match receiver {
+        Value::Result(result) => result_method(result, name, args),
+        Value::File(file) => fs::file_method(file, name, args),
+        Value::String(s) => string::string_method(s, name, args),
+        _ => Err(undefined_method(receiver, name)),
+    }
+}
+
+fn undefined_method(receiver: &Value, name: &str) -> String {
+    format!("Undefined method '{}' on {}", name, receiver.type_name())
+}
+
+fn result_method(
+    result: &Result<Value, Value>,
+    name: &str,
+    args: &[Value],
+) -> Result<Value, String> {
+    match name {
+        "is_ok" => {
+            arity(name, args, 0)?;
+            Ok(Value::Bool(result.is_ok()))
+        }
+        "is_err" => {
+            arity(name, args, 0)?;
+            Ok(Value::Bool(result.is_err()))
+        }
+        "unwrap" => {
+            arity(name, args, 0)?;
+            match result {
+                Ok(value) => Ok(value.clone()),
+                Err(error) => Err(format!("Called unwrap() on Err({})", error.repr())),
+            }
+        }
+        "unwrap_err" => {
+            arity(name, args, 0)?;
+            match result {
+                Ok(value) => Err(format!("Called unwrap_err() on Ok({})", value.repr())),
+                Err(error) => Ok(error.clone()),
+            }
+        }
+        "unwrap_or" => {
+            arity(name, args, 1)?;
+            Ok(result.clone().unwrap_or_else(|_| args[0].clone()))
+        }
+        // the value, or nil for the other variant
+        "ok" => {
+            arity(name, args, 0)?;
+            Ok(result.clone().unwrap_or(Value::Nil))
+        }
+        "err" => {
+            arity(name, args, 0)?;
+            Ok(result.clone().err().unwrap_or(Value::Nil))
+        }
+        _ => Err(format!("Undefined method '{}' on result", name)),
+    }
+}
@@ -0,0 +1,105 @@
+use crate::value::Value;
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Debug, Formatter};
+use std::path::{Path, PathBuf};
+
+/// a file of code and the globals it reads and writes. Each module is loaded
+/// once, the script being run is the main module
+pub struct Module {
+    pub name: String,
+    pub path: Option<PathBuf>, // none for code typed into the repl
+    pub globals: RefCell<HashMap<String, Value>>,
+}
+
+impl Module {
+    pub fn new(name: &str, path: Option<PathBuf>) -> Module {
+        Module {
+            name: String::from(name),
+            path,
+            globals: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// what importing the module gives: a map of its exported globals
+    pub fn exports(&self, names: &[String]) -> Value {
+        let globals = self.globals.borrow();
+        let exports: BTreeMap<String, Value> = names
+            .iter()
+            .map(|name| {
+                (
+                    name.clone(),
+                    globals.get(name).cloned().unwrap_or(Value::Nil),
+                )
+            })
+            .collect();
+        Value::from(exports)
+    }
+
+    /// directory the module's imports are relative to
+    pub fn dir(&self) -> PathBuf {
+        match self.path.as_ref().and_then(|path| path.parent()) {
+            Some(dir) => dir.to_path_buf(),
+            None => PathBuf::from("."),
+        }
+    }
+}
+
+// globals can hold functions that point back at their module
+impl Debug for Module {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "<module {}>", self.name)
+    }
+}
+
+/// name an import binds the module to, the file name without its extension
+pub fn module_name(spec: &str) -> &str {
+    let file = spec.rsplit('/').next().unwrap_or(spec);
+    file.strip_suffix(".tz").unwrap_or(file)
+}
+
+/// finds the file an import refers to. `pkg` is a dependency's entry module
+/// and `pkg/mod` a module beside it, other imports are looked for next to the
+/// importing module and then in each directory of the search path
+pub fn resolve(
+    spec: &str,
+    dir: &Path,
+    search_path: &[PathBuf],
+    packages: &HashMap<String, PathBuf>,
+) -> Option<PathBuf> {
+    let (first, rest) = match spec.split_once('/') {
+        Some((first, rest)) => (first, Some(rest)),
+        None => (spec, None),
+    };
+    if let Some(entry) = packages.get(first) {
+        let path = match rest {
+            Some(rest) => entry.parent()?.join(rest).with_extension("tz"),
+            None => entry.clone(),
+        };
+        return path.canonicalize().ok();
+    }
+
+    let file = if spec.ends_with(".tz") {
+        String::from(spec)
+    } else {
+        format!("{}.tz", spec)
+    };
+
+    std::iter::once(dir)
+        .chain(search_path.iter().map(PathBuf::as_path))
+        .map(|dir| dir.join(&file))
+        .find(|path| path.is_file())
+        .and_then(|path| path.canonicalize().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn module_name_is_the_file_name() {
+        assert_eq!(module_name("lib/util/strings"), "strings");
+        assert_eq!(module_name("math.tz"), "math");
+    }
+}
@@ -0,0 +1,133 @@
+use crate::bigint::BigInt;
+use crate::value::Value;
+
+use crate::vm::Vm;
+use std::rc::Rc;
+
+pub fn define_natives(vm: &mut Vm) {
+    vm.define_native("int", int);
+    vm.define_native("float", float);
+    vm.define_native("str", str);
+    vm.define_native("doc", doc);
+    vm.define_native("error", error);
+    vm.define_native("Ok", ok);
+    vm.define_native("Err", err);
+}
+
+/// checks that a native was called with the expected number of arguments
+pub fn arity(name: &str, args: &[Value], expected: usize) -> Result<(), String> {
+    if args.len() == expected {
+        Ok(())
+    } else {
+        Err(format!(
+            "{}() expects {} argument(s) but got {}",
+            name,
+            expected,
+            args.len()
+        ))
+    }
+}
+
+/// gets an argument that must be a string
+pub fn string_arg<'a>(name: &str, args: &'a [Value], index: usize) -> Result<&'a str, String> {
+    match &args[index] {
+        Value::String(s) => Ok(s),
+        value => Err(format!(
+            "{}() expects a string but got {}",
+            name,
+            value.type_name()
+        )),
+    }
+}
+
+/// gets an argument that must be an int small enough for an i64
+pub fn int_arg(name: &str, args: &[Value], index: usize) -> Result<i64, String> {
+    match &args[index] {
+        Value::Int(n) => Ok(*n),
+        value => Err(format!(
+            "{}() expects an int but got {}",
+            name,
+            value.type_name()
+        )),
+    }
+}
+
+/// gets an argument that must be a number, as a float
+pub fn number_arg(name: &str, args: &[Value], index: usize) -> Result<f64, String> {
+    match &args[index] {
+        Value::Int(n) => Ok(*n as f64),
+        Value::BigInt(n) => Ok(n.to_f64()),
+        Value::Number(x) => Ok(*x),
+        value => Err(format!(
+            "{}() expects a number but got {}",
+            name,
+            value.type_name()
+        )),
+    }
+}
+
+/// converts a number, bool or numeric string to an integer, truncating floats
+fn int(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    arity("int", args, 1)?;
+
+    match &args[0] {
+        Value::Int(_) | Value::BigInt(_) => Ok(args[0].clone()),
+        Value::Bool(b) => Ok(Value::Int(*b as i64)),
+        Value::Number(x) => match BigInt::from_f64(*x) {
+            Some(n) => Ok(Value::from(n)),
+            None => Err(format!("Cannot convert {} to int", x)),
+        },
+        Value::String(s) => match s.trim().parse::<BigInt>() {
+            Ok(n) => Ok(Value::from(n)),
+            Err(_) => Err(format!("Cannot convert '{}' to int", s)),
+        },
+        value => Err(format!("Cannot convert {} to int", value)),
+    }
+}
+
+fn float(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    arity("float", args, 1)?;
+
+    match &args[0] {
+        Value::Number(_) => Ok(args[0].clone()),
+        Value::Int(n) => Ok(Value::Number(*n as f64)),
+        Value::BigInt(n) => Ok(Value::Number(n.to_f64())),
+        Value::Bool(b) => Ok(Value::Number(*b as i64 as f64)),
+        Value::String(s) => match s.trim().parse::<f64>() {
+            Ok(x) => Ok(Value::Number(x)),
+            Err(_) => Err(format!("Cannot convert '{}' to float", s)),
+        },
+        value => Err(format!("Cannot convert {} to float", value)),
+    }
+}
+
+fn str(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    arity("str", args, 1)?;
+    Ok(Value::String(args[0].to_string()))
+}
+
+/// returns a function's doc comment, or nil if it has none
+fn doc(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    arity("doc", args, 1)?;
+
+    match &args[0] {
+        Value::Function(f) => Ok(f.doc.clone().map_or(Value::Nil, Value::String)),
+        value => Err(format!("Cannot get doc comment of {}", value)),
+    }
+}
+
+/// makes an error value like the ones runtime errors throw
+fn error(vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    arity("error", args, 1)?;
+    Ok(vm.error_value(&args[0].to_string()))
+}
+
+fn ok(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    arity("Ok", args, 1)?;
+    Ok(Value::Result(Rc::new(Ok(args[0].clone()))))
+}
+
+fn err(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    arity("Err", args, 1)?;
+    Ok(Value::Result(Rc::new(Err(args[0].clone()))))
+}
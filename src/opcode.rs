@@ -28,6 +28,39 @@ pub enum Opcode {
     Jump,
     Loop,
     Call,
+    IntDivide,
+    BitwiseXor,
+    ShiftLeft,
+    ShiftRight,
+    Concat,
+    Closure,
+    GetUpvalue,
+    SetUpvalue,
+    PopLocals,
+    BuildList,
+    BuildMap,
+    GetIndex,
+    SetIndex,
+    IsList,
+    IsMap,
+    HasKey,
+    InRange,
+    JumpTable,
+    MatchError,
+    ExpectList,
+    ExpectKey,
+    Duplicate,
+    GetField,
+    SetField,
+    PushHandler,
+    PopHandler,
+    Throw,
+    Invoke,
+    IsErr,
+    UnwrapOk,
+    Import,
+    Power,
+    EndFinally,
 
     Unknown,
 }
@@ -64,6 +97,39 @@ impl Opcode {
             26 => Opcode::Jump,
             27 => Opcode::Loop,
             28 => Opcode::Call,
+            29 => Opcode::IntDivide,
+            30 => Opcode::BitwiseXor,
+            31 => Opcode::ShiftLeft,
+            32 => Opcode::ShiftRight,
+            33 => Opcode::Concat,
+            34 => Opcode::Closure,
+            35 => Opcode::GetUpvalue,
+            36 => Opcode::SetUpvalue,
+            37 => Opcode::PopLocals,
+            38 => Opcode::BuildList,
+            39 => Opcode::BuildMap,
+            40 => Opcode::GetIndex,
+            41 => Opcode::SetIndex,
+            42 => Opcode::IsList,
+            43 => Opcode::IsMap,
+            44 => Opcode::HasKey,
+            45 => Opcode::InRange,
+            46 => Opcode::JumpTable,
+            47 => Opcode::MatchError,
+            48 => Opcode::ExpectList,
+            49 => Opcode::ExpectKey,
+            50 => Opcode::Duplicate,
+            51 => Opcode::GetField,
+            52 => Opcode::SetField,
+            53 => Opcode::PushHandler,
+            54 => Opcode::PopHandler,
+            55 => Opcode::Throw,
+            56 => Opcode::Invoke,
+            57 => Opcode::IsErr,
+            58 => Opcode::UnwrapOk,
+            59 => Opcode::Import,
+            60 => Opcode::Power,
+            61 => Opcode::EndFinally,
             _ => Opcode::Unknown,
         }
     }
@@ -6,6 +6,7 @@ pub enum Operator {
     Minus,
     Star,
     Slash,
+    SlashSlash,
     Mod,
     LessThan,
     GreaterThan,
@@ -13,6 +14,10 @@ pub enum Operator {
     AmpAmp,
     Pipe,
     PipePipe,
+    Caret,
+    LessLess,
+    GreaterGreater,
+    StarStar,
 }
 
 impl Display for Operator {
@@ -22,6 +27,7 @@ impl Display for Operator {
             Operator::Minus => write!(f, "-"),
             Operator::Star => write!(f, "*"),
             Operator::Slash => write!(f, "/"),
+            Operator::SlashSlash => write!(f, "//"),
             Operator::Mod => write!(f, "%"),
             Operator::LessThan => write!(f, "<"),
             Operator::GreaterThan => write!(f, ">"),
@@ -29,6 +35,10 @@ impl Display for Operator {
             Operator::AmpAmp => write!(f, "&&"),
             Operator::Pipe => write!(f, "|"),
             Operator::PipePipe => write!(f, "||"),
+            Operator::Caret => write!(f, "^"),
+            Operator::LessLess => write!(f, "<<"),
+            Operator::GreaterGreater => write!(f, ">>"),
+            Operator::StarStar => write!(f, "**"),
         }
     }
 }
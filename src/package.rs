@@ -0,0 +1,421 @@
+use crate::manifest::{Dependency, Manifest, Source, MANIFEST_FILE};
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+pub const LOCK_FILE: &str = "topaz.lock";
+
+/// where archives are unpacked, inside the project directory
+const DEPS_DIR: &str = ".topaz/deps";
+
+/// a dependency resolved to a directory on disk
+#[derive(Debug)]
+pub struct Package {
+    pub name: String,
+    pub version: String,
+    pub entry: PathBuf,       // the module `import name` loads
+    source: String,           // where it came from, relative to the project
+    archive: Option<PathBuf>, // unpacked by `install`
+    checksum: u64,
+}
+
+/// file contents by path, sorted so hashing them is repeatable
+type Files = BTreeMap<PathBuf, Vec<u8>>;
+
+/// a path in an archive with the file's contents, or none for a directory
+type TarEntry = (PathBuf, Option<Vec<u8>>);
+
+/// finds the project containing a directory by looking for a manifest in
+/// it and each of its parents
+pub fn find_root(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .find(|dir| dir.join(MANIFEST_FILE).is_file())
+        .map(Path::to_path_buf)
+}
+
+/// resolves the project's dependencies and theirs. A package can only come
+/// from one place. Archives are read without unpacking them, so nothing is
+/// written until `install`
+pub fn resolve(root: &Path, manifest: &Manifest) -> Result<Vec<Package>, String> {
+    let mut packages: BTreeMap<String, Package> = BTreeMap::new();
+    let mut archives: BTreeMap<PathBuf, Files> = BTreeMap::new(); // by where each will be unpacked
+
+    // each package's directory relative to the project, its name and its dependencies
+    let mut queue = vec![(
+        PathBuf::new(),
+        manifest.name.clone(),
+        manifest.dependencies.clone(),
+    )];
+
+    while let Some((dir, dependent, deps)) = queue.pop() {
+        for Dependency { name, source } in deps {
+            let (path, source, archive) = match source {
+                Source::Path(path) => {
+                    let path = normalize(&dir.join(path));
+                    (path.clone(), format!("path:{}", path.display()), None)
+                }
+                Source::Archive(archive) => {
+                    let archive = normalize(&dir.join(archive));
+                    let source = format!("archive:{}", archive.display());
+                    (Path::new(DEPS_DIR).join(&name), source, Some(archive))
+                }
+            };
+
+            if let Some(package) = packages.get(&name) {
+                if package.source != source {
+                    let msg = format!(
+                        "Dependency '{}' of '{}' is {} but was already resolved to {}",
+                        name, dependent, source, package.source
+                    );
+                    return Err(msg);
+                }
+                continue;
+            }
+
+            let path = match &archive {
+                Some(archive) => {
+                    let files: Files = read_tar(&root.join(archive))?
+                        .into_iter()
+                        .filter_map(|(path, contents)| Some((path, contents?)))
+                        .collect();
+                    let inner = package_dir(&files);
+                    archives.insert(path.clone(), files);
+                    path.join(inner)
+                }
+                None => path,
+            };
+
+            let files = package_files(root, &path, &archives);
+            let manifest_path = root.join(&path).join(MANIFEST_FILE);
+            let text = files
+                .get(Path::new(MANIFEST_FILE))
+                .ok_or_else(|| format!("Cannot read {}", manifest_path.display()))?;
+            let manifest = Manifest::parse(&String::from_utf8_lossy(text))
+                .map_err(|msg| format!("{}: {}", manifest_path.display(), msg))?;
+            if manifest.name != name {
+                let msg = format!(
+                    "Dependency '{}' is a package named '{}'",
+                    name, manifest.name
+                );
+                return Err(msg);
+            }
+
+            packages.insert(
+                name.clone(),
+                Package {
+                    name: name.clone(),
+                    version: manifest.version.clone(),
+                    entry: root.join(&path).join(&manifest.entry),
+                    source,
+                    archive,
+                    checksum: checksum(&files),
+                },
+            );
+            queue.push((path, name, manifest.dependencies));
+        }
+    }
+
+    Ok(packages.into_values().collect())
+}
+
+/// unpacks the archive dependencies into the project's .topaz directory,
+/// replacing the copies from earlier runs
+pub fn install(root: &Path, packages: &[Package]) -> Result<(), String> {
+    for package in packages {
+        if let Some(archive) = &package.archive {
+            let dest = root.join(DEPS_DIR).join(&package.name);
+            let _ = fs::remove_dir_all(&dest);
+            extract_tar(&root.join(archive), &dest)?;
+        }
+    }
+    Ok(())
+}
+
+/// archives usually hold the package in a single top level directory
+fn package_dir(files: &Files) -> PathBuf {
+    let top = |path: &Path| match path.components().count() {
+        1 => None,
+        _ => path
+            .components()
+            .next()
+            .map(|c| PathBuf::from(c.as_os_str())),
+    };
+
+    match files.keys().next().and_then(|path| top(path)) {
+        Some(dir) if files.keys().all(|path| top(path).as_ref() == Some(&dir)) => dir,
+        _ => PathBuf::new(),
+    }
+}
+
+/// the files in a package directory by their path inside it, leaving out
+/// hidden files and the lockfile. Directories inside an archive that's
+/// being resolved are read from the archive
+fn package_files(root: &Path, dir: &Path, archives: &BTreeMap<PathBuf, Files>) -> Files {
+    fn walk(dir: &Path, base: &Path, out: &mut Files) {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries.flatten().map(|entry| entry.path()),
+            Err(_) => return,
+        };
+
+        for path in entries {
+            let name = path.strip_prefix(base).unwrap().to_path_buf();
+            if is_skipped(&name) {
+                continue;
+            }
+
+            if path.is_dir() {
+                walk(&path, base, out);
+            } else {
+                out.insert(name, fs::read(&path).unwrap_or_default());
+            }
+        }
+    }
+
+    for (dest, files) in archives {
+        if let Ok(inner) = dir.strip_prefix(dest) {
+            return files
+                .iter()
+                .filter_map(|(path, contents)| {
+                    let name = path.strip_prefix(inner).ok()?;
+                    (!is_skipped(name)).then(|| (name.to_path_buf(), contents.clone()))
+                })
+                .collect();
+        }
+    }
+
+    let mut files = Files::new();
+    walk(&root.join(dir), &root.join(dir), &mut files);
+    files
+}
+
+fn is_skipped(path: &Path) -> bool {
+    path.components().any(|c| {
+        let name = c.as_os_str().to_string_lossy();
+        name.starts_with('.') || name == LOCK_FILE
+    })
+}
+
+/// the lockfile records exactly which packages a project was run with
+pub fn lockfile(packages: &[Package]) -> String {
+    let mut lock = String::from("# generated by `topaz run`, do not edit\n");
+    for package in packages {
+        lock += &format!(
+            "\n[[package]]\nname = \"{}\"\nversion = \"{}\"\nsource = \"{}\"\nchecksum = \"{:016x}\"\n",
+            package.name, package.version, package.source, package.checksum
+        );
+    }
+    lock
+}
+
+/// fnv-1a hash of the names and contents of a package's files
+fn checksum(files: &Files) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    let mut add = |bytes: &[u8]| {
+        for byte in bytes {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    };
+    for (path, contents) in files {
+        let name = path.to_string_lossy().replace('\\', "/");
+        add(name.as_bytes());
+        add(&[0]);
+        add(contents);
+        add(&[0]);
+    }
+    hash
+}
+
+/// joins `..` and `.` components without touching the file system, keeping
+/// paths in the lockfile the same on every machine
+fn normalize(path: &Path) -> PathBuf {
+    let mut parts: Vec<Component> = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir if matches!(parts.last(), Some(Component::Normal(_))) => {
+                parts.pop();
+            }
+            component => parts.push(component),
+        }
+    }
+    parts.iter().collect()
+}
+
+/// unpacks the regular files and directories of an uncompressed tar archive
+fn extract_tar(archive: &Path, dest: &Path) -> Result<(), String> {
+    for (path, contents) in read_tar(archive)? {
+        let write = match contents {
+            Some(contents) => fs::create_dir_all(dest.join(&path).parent().unwrap())
+                .and_then(|_| fs::write(dest.join(&path), contents)),
+            None => fs::create_dir_all(dest.join(&path)),
+        };
+        write.map_err(|e| {
+            format!(
+                "{}: Cannot unpack '{}': {}",
+                archive.display(),
+                path.display(),
+                e
+            )
+        })?;
+    }
+
+    Ok(())
+}
+
+/// reads the regular files and directories of an uncompressed tar archive
+fn read_tar(archive: &Path) -> Result<Vec<TarEntry>, String> {
+    let data =
+        fs::read(archive).map_err(|_| format!("Cannot read archive {}", archive.display()))?;
+    let error = |msg: &str| format!("{}: {}", archive.display(), msg);
+
+    let mut entries = Vec::new();
+    let mut offset = 0;
+    while offset + 512 <= data.len() {
+        let header = &data[offset..offset + 512];
+        if header.iter().all(|b| *b == 0) {
+            break;
+        }
+
+        let field = |range: std::ops::Range<usize>| {
+            let bytes = &header[range];
+            let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+            String::from_utf8_lossy(&bytes[..end]).into_owned()
+        };
+        let size = usize::from_str_radix(field(124..136).trim(), 8)
+            .map_err(|_| error("Invalid size in tar header"))?;
+        let mut name = field(0..100);
+        if field(257..262) == "ustar" && !field(345..500).is_empty() {
+            name = format!("{}/{}", field(345..500), name);
+        }
+
+        let start = offset + 512;
+        let end = start + size;
+        if end > data.len() {
+            return Err(error("Archive is truncated"));
+        }
+
+        let path = Path::new(&name);
+        if !path
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+        {
+            return Err(error(&format!(
+                "Refusing to unpack '{}' outside the package",
+                name
+            )));
+        }
+
+        let path = normalize(path);
+        match header[156] {
+            b'0' | 0 => entries.push((path, Some(data[start..end].to_vec()))),
+            b'5' => entries.push((path, None)),
+            _ => (), // links and extended headers aren't needed for packages
+        }
+
+        offset = start + size.div_ceil(512) * 512;
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a tar entry header with just the fields read_tar reads
+    fn tar_entry(name: &str, contents: &[u8]) -> Vec<u8> {
+        let mut header = vec![0; 512];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        let size = format!("{:011o}\0", contents.len());
+        header[124..136].copy_from_slice(size.as_bytes());
+        header[156] = b'0';
+
+        let mut entry = header;
+        entry.extend_from_slice(contents);
+        entry.resize(entry.len().div_ceil(512) * 512, 0);
+        entry
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("topaz-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn normalizes_paths_lexically() {
+        assert_eq!(normalize(Path::new("a/./b/../c")), PathBuf::from("a/c"));
+        assert_eq!(normalize(Path::new("../x/../y")), PathBuf::from("../y"));
+    }
+
+    #[test]
+    fn resolves_path_and_archive_dependencies() {
+        let root = temp_dir("resolve");
+        let manifest = |name: &str, deps: &str| {
+            format!(
+                "[package]\nname = \"{}\"\nversion = \"1.0\"\n[dependencies]\n{}",
+                name, deps
+            )
+        };
+
+        fs::create_dir_all(root.join("app/vendor")).unwrap();
+        fs::create_dir_all(root.join("shapes")).unwrap();
+        fs::write(
+            root.join("app/topaz.toml"),
+            manifest("app", "shapes = \"../shapes\""),
+        )
+        .unwrap();
+        fs::write(
+            root.join("shapes/topaz.toml"),
+            manifest(
+                "shapes",
+                "colors = { archive = \"../app/vendor/colors.tar\" }",
+            ),
+        )
+        .unwrap();
+
+        let mut tar = tar_entry("colors/topaz.toml", manifest("colors", "").as_bytes());
+        tar.extend(tar_entry("colors/main.tz", b"export red = 1\n"));
+        tar.extend(vec![0; 1024]);
+        fs::write(root.join("app/vendor/colors.tar"), tar).unwrap();
+
+        let app = root.join("app");
+        let packages = resolve(&app, &Manifest::load(&app).unwrap()).unwrap();
+        let names: Vec<&str> = packages.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["colors", "shapes"]);
+        assert_eq!(
+            packages[0].entry,
+            app.join(".topaz/deps/colors/colors/main.tz")
+        );
+
+        // resolving writes nothing, so a stale lockfile can be caught first
+        assert!(!app.join(".topaz").exists());
+        install(&app, &packages).unwrap();
+        assert!(packages[0].entry.is_file());
+
+        let lock = lockfile(&packages);
+        assert!(lock.contains("source = \"archive:../app/vendor/colors.tar\""));
+        assert!(lock.contains("source = \"path:../shapes\""));
+        assert_eq!(
+            lock,
+            lockfile(&resolve(&app, &Manifest::load(&app).unwrap()).unwrap())
+        );
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn refuses_archive_paths_outside_the_package() {
+        let root = temp_dir("unsafe-tar");
+        let mut tar = tar_entry("../evil.tz", b"");
+        tar.extend(vec![0; 1024]);
+        fs::write(root.join("evil.tar"), tar).unwrap();
+
+        let err = extract_tar(&root.join("evil.tar"), &root.join("out")).unwrap_err();
+        assert!(err.contains("Refusing to unpack '../evil.tz'"));
+        fs::remove_dir_all(root).unwrap();
+    }
+}
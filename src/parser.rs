@@ -1,4 +1,6 @@
+use crate::chunk::Chunk;
 use crate::function::{Function, FunctionType};
+use crate::module;
 use crate::opcode::Opcode;
 use crate::precedence::Precedence;
 use crate::token::{Token, TokenType};
@@ -6,16 +8,25 @@ use crate::value::Value;
 use crate::vm::InterpretError;
 use crate::Scanner;
 
+use std::rc::Rc;
+
 pub struct Parser {
     current: Token,
     previous: Token,
     scanner: Scanner,
-    functions: Vec<Function>,
-    locals: Vec<Local>,
+    compilers: Vec<Compiler>, // the innermost function being compiled is last
+    exports: Vec<String>,
     had_error: bool,
+    panic_mode: bool,
     end_flag: bool,
-    local_count: usize,
+}
+
+/// state of a single function being compiled
+struct Compiler {
+    function: Function,
+    locals: Vec<Local>,
     scope_depth: usize,
+    upvalues: Vec<UpvalueRef>,
 }
 
 /// represents a local variable
@@ -24,73 +35,242 @@ struct Local {
     depth: usize,
 }
 
+/// a pattern in a `match` arm, compiled to tests against the matched value
+/// followed by bindings for the variables it names
+enum Pattern {
+    Wildcard,
+    Binding(String),
+    Literal(Value),
+    Range(Value, Value, bool), // inclusive of the upper bound when set
+    List(Vec<Pattern>),
+    Map(Vec<(String, Pattern)>),
+}
+
+/// a variable captured by the function being compiled, either a local slot of
+/// the directly enclosing function or one of that function's own upvalues
+#[derive(PartialEq)]
+struct UpvalueRef {
+    index: usize,
+    is_local: bool,
+}
+
+impl Compiler {
+    fn new(function: Function) -> Compiler {
+        // slot 0 holds the function being called, it can't be named
+        let callee = Local {
+            name: String::new(),
+            depth: 0,
+        };
+
+        Compiler {
+            function,
+            locals: vec![callee],
+            scope_depth: 0,
+            upvalues: Vec::new(),
+        }
+    }
+
+    fn resolve_local(&self, name: &str) -> Option<usize> {
+        self.locals.iter().rposition(|local| local.name == name)
+    }
+
+    fn add_upvalue(&mut self, upvalue: UpvalueRef) -> usize {
+        match self.upvalues.iter().position(|u| *u == upvalue) {
+            Some(index) => index,
+            None => {
+                self.upvalues.push(upvalue);
+                self.upvalues.len() - 1
+            }
+        }
+    }
+}
+
 impl Parser {
     pub fn new(source: String) -> Parser {
         Parser {
             current: Token::new(TokenType::Error(String::from("current token")), 0, 0, 0),
             previous: Token::new(TokenType::Error(String::from("current token")), 0, 0, 0),
             scanner: Scanner::new(source),
-            functions: Vec::new(),
-            locals: Vec::new(),
+            compilers: Vec::new(),
+            exports: Vec::new(),
             had_error: false,
+            panic_mode: false,
             end_flag: false,
-            local_count: 0,
-            scope_depth: 0,
         }
     }
 
-    pub fn compile(mut self) -> Result<Function, InterpretError> {
-        // add top level functions to function stack
-        self.functions
-            .push(Function::new(String::new(), FunctionType::Script));
+    pub fn compile(self) -> Result<Function, InterpretError> {
+        self.compile_module().map(|(function, _)| function)
+    }
+
+    /// compiles a module's script, also giving the names it exports
+    pub fn compile_module(mut self) -> Result<(Function, Vec<String>), InterpretError> {
+        // the top level script is compiled like the body of a function
+        self.compilers.push(Compiler::new(Function::new(
+            String::new(),
+            FunctionType::Script,
+        )));
 
         self.advance();
 
-        while !self.end_flag {
-            self.declaration();
+        // the script returns the value of its last expression statement
+        let mut has_value = false;
+        loop {
+            self.skip_separators();
+            if self.end_flag {
+                break;
+            }
+
+            has_value = self.declaration();
         }
 
+        self.keep_last_value(has_value);
         self.emit_op(Opcode::Return);
-        Ok(self.functions[0].clone())
+
+        if self.had_error {
+            return Err(InterpretError::CompileError);
+        }
+
+        Ok((self.compilers.pop().unwrap().function, self.exports))
     }
 
     fn expression(&mut self) {
         self.parse_precedence(Precedence::Assignment);
     }
 
-    fn declaration(&mut self) {
-        self.statement();
+    /// compiles a statement, returning whether it was an expression statement
+    /// whose value was popped
+    fn declaration(&mut self) -> bool {
+        let has_value = self.statement();
+
+        if self.panic_mode {
+            self.synchronize();
+        }
+
+        has_value
+    }
+
+    /// skips blank statements
+    fn skip_separators(&mut self) {
+        while self.matches(TokenType::Newline) || self.matches(TokenType::Semicolon) {}
+    }
+
+    /// skips tokens until the start of the next statement after a syntax error
+    fn synchronize(&mut self) {
+        self.panic_mode = false;
+
+        while !self.end_flag {
+            match self.current.token_type {
+                TokenType::Newline | TokenType::Semicolon => {
+                    self.advance();
+                    return;
+                }
+                TokenType::Fn
+                | TokenType::If
+                | TokenType::While
+                | TokenType::Print
+                | TokenType::Return
+                | TokenType::RightBrace => return,
+                _ => self.advance(),
+            }
+        }
     }
 
-    fn statement(&mut self) {
+    /// statements leave the stack as they found it. Blocks, `if` and
+    /// expression statements pop their value and return true
+    fn statement(&mut self) -> bool {
+        if self.is_destructuring() {
+            self.destructuring_assignment();
+            self.end_statement();
+            return false;
+        }
+
         match self.current.token_type.clone() {
             TokenType::Print => {
                 self.advance();
                 self.print_statement();
+                self.end_statement();
+                false
             }
             TokenType::LeftBrace => {
                 self.advance();
-                self.begin_scope();
                 self.block();
-                self.end_scope();
+                self.emit_op(Opcode::Pop);
+                true
             }
             TokenType::If => {
                 self.advance();
-                self.if_statement();
+                self.if_expression(false);
+                self.emit_op(Opcode::Pop);
+                true
+            }
+            TokenType::Try => {
+                self.advance();
+                self.try_expression(false);
+                self.emit_op(Opcode::Pop);
+                true
             }
             TokenType::While => {
                 self.advance();
                 self.while_statement();
+                false
             }
             TokenType::Fn => {
                 self.advance();
-                self.function_definition();
+
+                // `fn` without a name starts an anonymous function expression
+                if let TokenType::Identifier(_) = self.current.token_type {
+                    self.function_definition();
+                    false
+                } else {
+                    self.lambda(true);
+                    self.parse_infix(Precedence::Assignment);
+                    self.emit_op(Opcode::Pop);
+                    self.end_statement();
+                    true
+                }
             }
             TokenType::Return => {
                 self.advance();
                 self.return_statement();
+                self.end_statement();
+                false
+            }
+            TokenType::Import => {
+                self.advance();
+                self.import_statement();
+                self.end_statement();
+                false
             }
-            _ => self.expression_statement(),
+            TokenType::From => {
+                self.advance();
+                self.selective_import_statement();
+                self.end_statement();
+                false
+            }
+            TokenType::Export => {
+                self.advance();
+                self.export_statement()
+            }
+            _ => {
+                self.expression_statement();
+                self.end_statement();
+                true
+            }
+        }
+    }
+
+    /// simple statements end at a newline, a ';', the end of their block or
+    /// the end of the file
+    fn end_statement(&mut self) {
+        if self.end_flag {
+            return;
+        }
+
+        match self.current.token_type {
+            TokenType::Newline | TokenType::Semicolon => self.advance(),
+            TokenType::RightBrace => (),
+            _ => self.error_at_current("Expect newline or ';' after statement"),
         }
     }
 
@@ -100,79 +280,338 @@ impl Parser {
             _ => unreachable!("Not given an identifier in function_definition"),
         };
 
-        let f = Function::new(function_name, FunctionType::Fn);
-        self.functions.push(f);
+        // functions nested in a block live in the slot hoisted for them
+        let slot = self.hoisted_slot(&function_name);
+
+        let mut f = Function::new(function_name.clone(), FunctionType::Fn);
+        f.doc = self.scanner.doc_comment(self.previous.line);
 
         self.advance();
 
-        self.begin_scope();
         self.consume(TokenType::LeftParen, "Expect '(' after function name");
+        let (f, upvalues) = self.function_body(f);
+        self.emit_closure(f, upvalues);
 
-        let mut num_params = 0;
-        while !self.matches(TokenType::RightParen) {
-            num_params += 1;
-            match self.current.token_type.clone() {
-                TokenType::Identifier(name) => self.add_local(name),
-                _ => panic!("Expect identifier"),
+        match slot {
+            Some(slot) => {
+                self.emit_op(Opcode::SetLocal);
+                self.emit_byte(slot as u8);
+            }
+            None => {
+                let global = self.make_constant(Value::String(function_name));
+                self.emit_op(Opcode::SetGlobal);
+                self.emit_byte(global as u8);
             }
-            self.advance();
         }
 
-        let mut f = self.functions.pop().unwrap();
-        f.num_params = num_params;
-        self.functions.push(f);
+        self.emit_op(Opcode::Pop);
+    }
+
+    /// compiles an anonymous `fn (params) { body }` into a function value
+    pub fn lambda(&mut self, _can_assign: bool) {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'fn'");
+        let (f, upvalues) = self.function_body(Function::new(String::new(), FunctionType::Fn));
+        self.emit_closure(f, upvalues);
+    }
+
+    /// compiles the short form `|params| expr`, or `|| expr` without parameters
+    pub fn arrow_lambda(&mut self, _can_assign: bool) {
+        self.begin_function(Function::new(String::new(), FunctionType::Fn));
+        if self.previous.token_type == TokenType::BitwiseOr {
+            self.parameters(TokenType::BitwiseOr);
+        }
+
+        self.expression();
+        self.emit_op(Opcode::Return);
+
+        let (f, upvalues) = self.end_function();
+        self.emit_closure(f, upvalues);
+    }
+
+    /// compiles the parameters after '(' and the braced body of a function.
+    /// The body's value is returned implicitly
+    fn function_body(&mut self, f: Function) -> (Function, Vec<UpvalueRef>) {
+        self.begin_function(f);
+        self.parameters(TokenType::RightParen);
 
         self.consume(TokenType::LeftBrace, "Expect '{' before function body");
 
         self.block();
+        self.emit_op(Opcode::Return);
+
+        self.end_function()
+    }
 
-        // add implicit nil for empty functions
-        if self.functions.last_mut().unwrap().chunk.code.is_empty() {
-            self.emit_constant(Value::Nil);
+    /// parses parameter names up to the closing token, optionally separated by commas
+    fn parameters(&mut self, closing: TokenType) {
+        let mut num_params = 0;
+        while !self.end_flag && !self.matches(closing.clone()) {
+            match self.current.token_type.clone() {
+                TokenType::Identifier(name) => self.add_local(name),
+                _ => {
+                    self.error_at_current("Expect parameter name");
+                    return;
+                }
+            }
+            num_params += 1;
+            self.advance();
+            self.matches(TokenType::Comma);
         }
 
-        self.emit_op(Opcode::Return);
+        self.compiler().function.num_params = num_params;
+    }
+
+    /// starts compiling a function with its own locals, the enclosing
+    /// functions' compilers stay below it so their variables can be captured
+    fn begin_function(&mut self, f: Function) {
+        self.compilers.push(Compiler::new(f));
+        self.begin_scope();
+    }
 
-        let f = self.functions.pop().unwrap();
+    /// pops the finished function along with the variables it captures
+    fn end_function(&mut self) -> (Function, Vec<UpvalueRef>) {
+        let compiler = self.compilers.pop().unwrap();
+
+        let mut f = compiler.function;
+        f.upvalue_count = compiler.upvalues.len();
         f.chunk.disassemble(&f.name);
-        let global = self.make_constant(Value::Function(f));
-        self.emit_op(Opcode::SetGlobal);
-        self.emit_byte(global as u8);
+        (f, compiler.upvalues)
+    }
+
+    fn compiler(&mut self) -> &mut Compiler {
+        self.compilers.last_mut().unwrap()
+    }
+
+    fn chunk(&mut self) -> &mut Chunk {
+        &mut self.compiler().function.chunk
+    }
+
+    /// emits a function value, followed by where to find each variable it captures
+    fn emit_closure(&mut self, f: Function, upvalues: Vec<UpvalueRef>) {
+        let constant = self.make_constant(Value::Function(Rc::new(f)));
+        self.emit_bytes(Opcode::Closure as u8, constant as u8);
+
+        for upvalue in upvalues {
+            self.emit_bytes(upvalue.is_local as u8, upvalue.index as u8);
+        }
     }
 
     fn expression_statement(&mut self) {
         self.expression();
+        self.emit_op(Opcode::Pop);
     }
 
+    /// compiles the statements of a block in a new scope, leaving the value
+    /// of its last expression statement, or nil, on the stack
     fn block(&mut self) {
-        while self.current.token_type != TokenType::RightBrace {
-            self.declaration()
+        self.begin_scope();
+        self.hoist_functions();
+
+        let mut has_value = false;
+        loop {
+            self.skip_separators();
+            if self.end_flag || self.current.token_type == TokenType::RightBrace {
+                break;
+            }
+
+            has_value = self.declaration();
         }
 
         self.consume(TokenType::RightBrace, "Expect '}' after block");
+        self.keep_last_value(has_value);
+        self.end_scope();
     }
 
-    fn if_statement(&mut self) {
+    /// keeps the value of a trailing expression statement by dropping its pop,
+    /// or pushes nil when the last statement had no value
+    fn keep_last_value(&mut self, has_value: bool) {
+        if has_value {
+            let chunk = self.chunk();
+            chunk.code.pop();
+            chunk.lines.pop();
+        } else {
+            self.emit_op(Opcode::Nil);
+        }
+    }
+
+    /// declares the functions defined directly in the block about to be
+    /// compiled, so siblings can call each other before their definitions
+    fn hoist_functions(&mut self) {
+        let mut scanner = self.scanner.clone();
+        let mut token = Some(self.current.clone()).filter(|_| !self.end_flag);
+        let mut after_fn = false;
+        let mut depth = 0;
+        let mut names = Vec::new();
+
+        while let Some(tok) = token {
+            match tok.token_type {
+                TokenType::LeftBrace => depth += 1,
+                TokenType::RightBrace if depth == 0 => break,
+                TokenType::RightBrace => depth -= 1,
+                TokenType::Identifier(ref name) if after_fn && depth == 0 => {
+                    names.push(name.clone())
+                }
+                _ => (),
+            }
+
+            after_fn = tok.token_type == TokenType::Fn;
+            token = scanner.next();
+        }
+
+        for name in names {
+            self.add_local(name);
+            self.emit_op(Opcode::Nil);
+        }
+    }
+
+    /// slot of a function hoisted into the current block
+    fn hoisted_slot(&self, name: &str) -> Option<usize> {
+        let compiler = self.compilers.last().unwrap();
+        if compiler.scope_depth == 0 {
+            return None;
+        }
+
+        compiler
+            .locals
+            .iter()
+            .rposition(|local| local.depth == compiler.scope_depth && local.name == name)
+    }
+
+    /// `{ ... }` used as an expression has the value of its last expression
+    /// statement, or nil
+    pub fn block_expression(&mut self, _can_assign: bool) {
+        self.block();
+    }
+
+    /// `if` leaves the value of the branch taken, nil when no branch is taken
+    pub fn if_expression(&mut self, _can_assign: bool) {
         self.expression();
         let if_offset = self.emit_jump(Opcode::JumpIfFalse);
         self.emit_op(Opcode::Pop);
-        self.statement();
+        self.consume(TokenType::LeftBrace, "Expect '{' after condition");
+        self.block();
 
         let else_offset = self.emit_jump(Opcode::Jump);
-        self.emit_op(Opcode::Pop);
 
         self.patch_jump(if_offset);
+        self.emit_op(Opcode::Pop);
 
         // compile optional else clause
         if self.matches(TokenType::Else) {
-            self.statement();
+            if self.matches(TokenType::If) {
+                self.if_expression(false);
+            } else {
+                self.consume(TokenType::LeftBrace, "Expect '{' after else");
+                self.block();
+            }
+        } else {
+            self.emit_op(Opcode::Nil);
         }
 
         self.patch_jump(else_offset);
     }
 
+    /// `try` leaves the value of its block, or of the catch block when the try
+    /// block throws. The finally block runs either way, then an error nothing
+    /// caught is thrown again
+    pub fn try_expression(&mut self, _can_assign: bool) {
+        self.consume(TokenType::LeftBrace, "Expect '{' after try");
+        let (handler, try_finally) = self.emit_handler();
+        self.block();
+        self.emit_op(Opcode::PopHandler);
+        let mut done_jumps = vec![self.emit_jump(Opcode::Jump)];
+
+        // the handler leaves the error where the try block's value would be
+        self.patch_jump(handler);
+        let has_catch = self.matches(TokenType::Catch);
+        let mut catch_finally = None;
+        if has_catch {
+            let (handler, finally) = self.emit_handler();
+            catch_finally = Some(finally);
+            self.begin_scope();
+            match self.current.token_type.clone() {
+                TokenType::Identifier(name) => {
+                    self.advance();
+                    self.add_local(name);
+                }
+                _ => {
+                    self.add_local(String::new());
+                }
+            }
+            self.consume(TokenType::LeftBrace, "Expect '{' after catch");
+            self.block();
+            self.end_scope();
+            self.emit_op(Opcode::PopHandler);
+            done_jumps.push(self.emit_jump(Opcode::Jump));
+
+            // an error thrown by the catch block replaces the one it caught
+            self.patch_jump(handler);
+            self.emit_bytes(Opcode::PopLocals as u8, 1);
+        }
+
+        if !self.matches(TokenType::Finally) {
+            if !has_catch {
+                self.error_at_current("Expect 'catch' or 'finally' after try block");
+            }
+            self.emit_op(Opcode::Throw);
+            for jump in done_jumps {
+                self.patch_jump(jump);
+            }
+            return;
+        }
+
+        // a flag on top of the value or error says whether to throw again,
+        // or nil to carry on returning the value from the function
+        self.emit_op(Opcode::True);
+        let mut finally_jumps = vec![self.emit_jump(Opcode::Jump)];
+        self.patch_jump(try_finally);
+        self.emit_op(Opcode::Nil);
+        finally_jumps.push(self.emit_jump(Opcode::Jump));
+        if let Some(catch_finally) = catch_finally {
+            self.patch_jump(catch_finally);
+            self.emit_bytes(Opcode::PopLocals as u8, 1);
+            self.emit_op(Opcode::Nil);
+            finally_jumps.push(self.emit_jump(Opcode::Jump));
+        }
+        for jump in done_jumps {
+            self.patch_jump(jump);
+        }
+        self.emit_op(Opcode::False);
+        for jump in finally_jumps {
+            self.patch_jump(jump);
+        }
+
+        self.begin_scope();
+        self.add_local(String::new());
+        self.add_local(String::new());
+        self.consume(TokenType::LeftBrace, "Expect '{' after finally");
+        self.block();
+        self.emit_op(Opcode::Pop);
+
+        // the value and flag are consumed below rather than by end_scope
+        let compiler = self.compiler();
+        compiler.scope_depth -= 1;
+        compiler.locals.truncate(compiler.locals.len() - 2);
+
+        self.emit_op(Opcode::EndFinally);
+    }
+
+    /// pushes a handler that jumps to the catch when something is thrown. The
+    /// second jump, to the finally block on return, stays 0 unless patched
+    fn emit_handler(&mut self) -> (usize, usize) {
+        let catch = self.emit_jump(Opcode::PushHandler);
+        self.emit_bytes(0, 0);
+        (catch, catch + 2)
+    }
+
+    /// `throw` raises a value for the nearest enclosing `catch`
+    pub fn throw(&mut self, _can_assign: bool) {
+        self.expression();
+        self.emit_op(Opcode::Throw);
+    }
+
     fn while_statement(&mut self) {
-        let loop_start = self.functions.last_mut().unwrap().chunk.code.len();
+        let loop_start = self.chunk().code.len();
         self.expression();
         let exit_offset = self.emit_jump(Opcode::JumpIfFalse);
         self.emit_op(Opcode::Pop);
@@ -183,20 +622,147 @@ impl Parser {
         self.emit_op(Opcode::Pop);
     }
 
+    /// `import "path/to/mod"` binds the module's exports to `mod`
+    fn import_statement(&mut self) {
+        let spec = self.module_spec("import");
+        let name = String::from(module::module_name(&spec));
+        if !is_identifier(&name) {
+            self.error(&format!("Cannot bind module '{}' to a name", spec));
+        }
+
+        self.emit_import(spec);
+        self.set_variable(name);
+    }
+
+    /// `from mod import a, b` binds some of a module's exports
+    fn selective_import_statement(&mut self) {
+        let spec = self.module_spec("from");
+        self.consume(TokenType::Import, "Expect 'import' after module");
+        self.emit_import(spec);
+
+        loop {
+            let name = match self.current.token_type.clone() {
+                TokenType::Identifier(name) => name,
+                _ => {
+                    self.error_at_current("Expect name to import");
+                    return;
+                }
+            };
+            self.advance();
+
+            let constant = self.make_constant(Value::String(name.clone())) as u8;
+            self.emit_bytes(Opcode::Duplicate as u8, 1);
+            self.emit_bytes(Opcode::GetField as u8, constant);
+            self.set_variable(name);
+
+            if !self.matches(TokenType::Comma) {
+                break;
+            }
+        }
+
+        self.emit_op(Opcode::Pop);
+    }
+
+    /// a module is named by a string path, or by an identifier for modules
+    /// on the search path
+    fn module_spec(&mut self, keyword: &str) -> String {
+        let spec = match self.current.token_type.clone() {
+            TokenType::String(spec) | TokenType::Identifier(spec) => spec,
+            _ => {
+                self.error_at_current(&format!("Expect module path after '{}'", keyword));
+                String::new()
+            }
+        };
+        self.advance();
+        spec
+    }
+
+    fn emit_import(&mut self, spec: String) {
+        let constant = self.make_constant(Value::String(spec)) as u8;
+        self.emit_bytes(Opcode::Import as u8, constant);
+    }
+
+    /// assigns the value on top of the stack to a variable and pops it
+    fn set_variable(&mut self, name: String) {
+        let (_, set_op, arg) = self.resolve_variable(name);
+        self.emit_bytes(set_op as u8, arg as u8);
+        self.emit_op(Opcode::Pop);
+    }
+
+    /// `export` in front of a function or an assignment, or before a list of
+    /// names, makes those globals importable
+    fn export_statement(&mut self) -> bool {
+        if self.compilers.len() > 1 || self.compiler().scope_depth > 0 {
+            self.error("Can only export from the top level of a module");
+        }
+
+        let name = match self.current.token_type.clone() {
+            TokenType::Fn => match self.scanner.clone().next().map(|t| t.token_type) {
+                Some(TokenType::Identifier(name)) => name,
+                _ => String::new(),
+            },
+            TokenType::Identifier(name) => name,
+            _ => {
+                self.error_at_current("Expect function, assignment or names after 'export'");
+                return false;
+            }
+        };
+
+        let assigns = matches!(
+            self.scanner.clone().next().map(|t| t.token_type),
+            Some(TokenType::Equal)
+        );
+        if self.current.token_type == TokenType::Fn || assigns {
+            self.exports.push(name);
+            return self.statement();
+        }
+
+        loop {
+            match self.current.token_type.clone() {
+                TokenType::Identifier(name) => self.exports.push(name),
+                _ => self.error_at_current("Expect name to export"),
+            }
+            self.advance();
+
+            if !self.matches(TokenType::Comma) {
+                break;
+            }
+        }
+
+        self.end_statement();
+        false
+    }
+
     fn return_statement(&mut self) {
-        // TODO: don't parse expression if return is followed immediately by \n
-        self.expression();
+        // a bare `return` returns nil
+        let bare = self.end_flag
+            || matches!(
+                self.current.token_type,
+                TokenType::Newline | TokenType::Semicolon | TokenType::RightBrace
+            );
+
+        if bare {
+            self.emit_op(Opcode::Nil);
+        } else {
+            self.expression();
+        }
+
         self.emit_op(Opcode::Return);
     }
 
     pub fn advance(&mut self) {
         self.previous = self.current.clone();
 
-        if let Some(tok) = self.scanner.next() {
+        // report error tokens from the scanner and skip past them
+        while let Some(tok) = self.scanner.next() {
             self.current = tok;
-        } else {
-            self.end_flag = true;
+            match self.current.token_type.clone() {
+                TokenType::Error(msg) => self.error_at_current(&msg),
+                _ => return,
+            }
         }
+
+        self.end_flag = true;
     }
 
     fn consume(&mut self, token_type: TokenType, msg: &str) {
@@ -214,6 +780,44 @@ impl Parser {
         }
     }
 
+    /// compiles an interpolated string into its segments and expressions,
+    /// joined by a single concatenation
+    pub fn interpolation(&mut self, _can_assign: bool) {
+        let mut parts = 0;
+
+        while let TokenType::Interpolation(segment) = self.previous.token_type.clone() {
+            if !segment.is_empty() {
+                self.emit_constant(Value::String(segment));
+                parts += 1;
+            }
+
+            self.expression();
+            parts += 1;
+
+            match self.current.token_type {
+                TokenType::Interpolation(_) | TokenType::String(_) => self.advance(),
+                _ => {
+                    self.error_at_current("Expect '}' after interpolated expression");
+                    return;
+                }
+            }
+        }
+
+        if let TokenType::String(segment) = self.previous.token_type.clone() {
+            if !segment.is_empty() {
+                self.emit_constant(Value::String(segment));
+                parts += 1;
+            }
+        }
+
+        if parts > u8::MAX as usize {
+            self.error("Too many parts in interpolated string");
+        }
+
+        self.emit_op(Opcode::Concat);
+        self.emit_byte(parts as u8);
+    }
+
     fn error(&mut self, msg: &str) {
         self.error_at(self.previous.clone(), msg);
     }
@@ -223,22 +827,28 @@ impl Parser {
     }
 
     fn error_at(&mut self, tok: Token, msg: &str) {
-        println!("[line {}] Error: {}", tok.line, msg);
+        // only report the first error until the parser resynchronizes
+        if self.panic_mode {
+            return;
+        }
+
+        self.panic_mode = true;
+        println!("[line {}:{}] Error: {}", tok.line, tok.col + 1, msg);
         self.had_error = true;
     }
 
     pub fn number(&mut self, _can_assign: bool) {
-        if let TokenType::Number(num) = self.previous.token_type {
-            self.emit_constant(Value::Number(num));
+        match self.previous.token_type.clone() {
+            TokenType::Number(num) => self.emit_constant(Value::Number(num)),
+            TokenType::Int(num) => self.emit_constant(Value::Int(num)),
+            TokenType::BigInt(num) => self.emit_constant(Value::BigInt(num)),
+            _ => unreachable!("No number"),
         }
     }
 
     fn emit_byte(&mut self, byte: u8) {
-        self.functions
-            .last_mut()
-            .unwrap()
-            .chunk
-            .write(byte, self.previous.line);
+        let line = self.previous.line;
+        self.chunk().write(byte, line);
     }
 
     fn emit_bytes(&mut self, a: u8, b: u8) {
@@ -263,25 +873,26 @@ impl Parser {
     fn emit_jump(&mut self, op: Opcode) -> usize {
         self.emit_byte(op as u8);
         self.emit_bytes(0xff, 0xff);
-        self.functions.last_mut().unwrap().chunk.code.len() - 2
+        self.chunk().code.len() - 2
     }
 
+    #[allow(clippy::legacy_numeric_constants)]
     fn patch_jump(&mut self, offset: usize) {
-        let jump = self.functions.last_mut().unwrap().chunk.code.len() - offset - 2;
+        let jump = self.chunk().code.len() - offset - 2;
 
         if jump > std::i16::MAX as usize {
             self.error("Jump is out of bounds");
         }
 
-        self.functions.last_mut().unwrap().chunk.code[offset] = ((jump >> 8) & 0xff) as u8;
-        self.functions.last_mut().unwrap().chunk.code[offset + 1] = (jump & 0xff) as u8;
+        self.chunk().code[offset] = ((jump >> 8) & 0xff) as u8;
+        self.chunk().code[offset + 1] = (jump & 0xff) as u8;
     }
 
     fn emit_loop(&mut self, loop_start: usize) {
         self.emit_op(Opcode::Loop);
 
-        let offset = self.functions.last_mut().unwrap().chunk.code.len() - loop_start + 2;
-        if offset as u16 > std::u16::MAX {
+        let offset = self.chunk().code.len() - loop_start + 2;
+        if offset as u16 > u16::MAX {
             self.error("Loop offset is out of bounds");
         }
 
@@ -290,8 +901,8 @@ impl Parser {
     }
 
     fn make_constant(&mut self, value: Value) -> usize {
-        let constant = self.functions.last_mut().unwrap().chunk.add_constant(value);
-        if constant > std::u8::MAX as usize {
+        let constant = self.chunk().add_constant(value);
+        if constant > u8::MAX as usize {
             self.error("Too many constants in this chunk");
             0
         } else {
@@ -315,12 +926,520 @@ impl Parser {
         }
     }
 
+    /// compiles a list literal `[a, b]`, or a map literal `['key': value]`
+    /// with `[:]` for an empty map
+    pub fn list(&mut self, _can_assign: bool) {
+        if self.matches(TokenType::Colon) {
+            self.consume(TokenType::RightBracket, "Expect ']' after ':' in empty map");
+            self.emit_bytes(Opcode::BuildMap as u8, 0);
+            return;
+        }
+
+        let mut count = 0;
+        let mut is_map = false;
+        while !self.end_flag && self.current.token_type != TokenType::RightBracket {
+            self.expression();
+
+            if count == 0 {
+                is_map = self.current.token_type == TokenType::Colon;
+            }
+
+            if is_map {
+                self.consume(TokenType::Colon, "Expect ':' after map key");
+                self.expression();
+            }
+
+            count += 1;
+            if !self.matches(TokenType::Comma) {
+                break;
+            }
+        }
+
+        self.consume(TokenType::RightBracket, "Expect ']' after elements");
+
+        if count > u8::MAX as usize {
+            self.error("Too many elements in literal");
+        }
+
+        let op = if is_map {
+            Opcode::BuildMap
+        } else {
+            Opcode::BuildList
+        };
+        self.emit_bytes(op as u8, count as u8);
+    }
+
+    pub fn subscript(&mut self, can_assign: bool) {
+        self.expression();
+        self.consume(TokenType::RightBracket, "Expect ']' after index");
+
+        if can_assign && self.matches(TokenType::Equal) {
+            self.expression();
+            self.emit_op(Opcode::SetIndex);
+        } else if let Some(op) = self.compound_assignment(can_assign) {
+            // keep the target and index for the store
+            self.emit_bytes(Opcode::Duplicate as u8, 2);
+            self.emit_op(Opcode::GetIndex);
+            self.expression();
+            self.emit_op(op);
+            self.emit_op(Opcode::SetIndex);
+        } else {
+            self.emit_op(Opcode::GetIndex);
+        }
+    }
+
+    /// `value.name` reads a field of a map, `value.name(args)` calls a method
+    pub fn dot(&mut self, can_assign: bool) {
+        let name = match self.current.token_type.clone() {
+            TokenType::Identifier(name) => name,
+            _ => {
+                self.error_at_current("Expect field name after '.'");
+                return;
+            }
+        };
+        self.advance();
+
+        let constant = self.make_constant(Value::String(name)) as u8;
+        if self.matches(TokenType::LeftParen) {
+            let num_args = self.arguments();
+            self.emit_bytes(Opcode::Invoke as u8, constant);
+            self.emit_byte(num_args);
+        } else if can_assign && self.matches(TokenType::Equal) {
+            self.expression();
+            self.emit_bytes(Opcode::SetField as u8, constant);
+        } else if let Some(op) = self.compound_assignment(can_assign) {
+            self.emit_bytes(Opcode::Duplicate as u8, 1);
+            self.emit_bytes(Opcode::GetField as u8, constant);
+            self.expression();
+            self.emit_op(op);
+            self.emit_bytes(Opcode::SetField as u8, constant);
+        } else {
+            self.emit_bytes(Opcode::GetField as u8, constant);
+        }
+    }
+
+    /// consumes a compound assignment operator like `+=`, returning the
+    /// opcode that combines the old and new values
+    fn compound_assignment(&mut self, can_assign: bool) -> Option<Opcode> {
+        if !can_assign {
+            return None;
+        }
+
+        let op = match self.current.token_type {
+            TokenType::PlusEqual => Opcode::Add,
+            TokenType::MinusEqual => Opcode::Subtract,
+            TokenType::StarEqual => Opcode::Multiply,
+            TokenType::SlashEqual => Opcode::Divide,
+            TokenType::ModEqual => Opcode::Mod,
+            TokenType::AmpEqual => Opcode::BitwiseAnd,
+            TokenType::PipeEqual => Opcode::BitwiseOr,
+            _ => return None,
+        };
+
+        self.advance();
+        Some(op)
+    }
+
+    /// compiles `match value { pattern => expr, ... }`. The arms are tried in
+    /// order, arms matching only ints become a jump table
+    pub fn match_expression(&mut self, _can_assign: bool) {
+        self.begin_scope();
+
+        // keep the matched value in a local no name can refer to
+        self.expression();
+        let subject = self.compiler().locals.len();
+        self.add_local(String::new());
+
+        self.consume(TokenType::LeftBrace, "Expect '{' after match value");
+
+        let mut end_jumps = Vec::new();
+        match self.int_arms() {
+            Some(arms) => self.match_table(arms, &mut end_jumps),
+            None => self.match_arms(subject, &mut end_jumps),
+        }
+
+        self.consume(TokenType::RightBrace, "Expect '}' after match arms");
+
+        // no arm matched
+        self.emit_bytes(Opcode::GetLocal as u8, subject as u8);
+        self.emit_op(Opcode::MatchError);
+
+        for jump in end_jumps {
+            self.patch_jump(jump);
+        }
+
+        self.end_scope();
+    }
+
+    fn skip_arm_separators(&mut self) {
+        while self.matches(TokenType::Newline) || self.matches(TokenType::Comma) {}
+    }
+
+    fn match_arms(&mut self, subject: usize, end_jumps: &mut Vec<usize>) {
+        loop {
+            self.skip_arm_separators();
+            if self.end_flag || self.current.token_type == TokenType::RightBrace {
+                break;
+            }
+
+            let pattern = self.pattern();
+
+            // each failed test leaves its false result on the stack
+            let mut fail_jumps = Vec::new();
+            self.test_pattern(&pattern, subject, &mut Vec::new(), &mut fail_jumps);
+
+            self.begin_scope();
+            let bindings = self.bind_pattern(&pattern, subject, &mut Vec::new());
+
+            let guard_jump = if self.matches(TokenType::If) {
+                self.expression();
+                let jump = self.emit_jump(Opcode::JumpIfFalse);
+                self.emit_op(Opcode::Pop);
+                Some(jump)
+            } else {
+                None
+            };
+
+            self.consume(TokenType::FatArrow, "Expect '=>' after pattern");
+            self.expression();
+            self.end_scope();
+            end_jumps.push(self.emit_jump(Opcode::Jump));
+
+            // a failed guard also drops the arm's bindings
+            let mut next_arm = None;
+            if let Some(jump) = guard_jump {
+                self.patch_jump(jump);
+                for _ in 0..=bindings {
+                    self.emit_op(Opcode::Pop);
+                }
+                next_arm = Some(self.emit_jump(Opcode::Jump));
+            }
+
+            if !fail_jumps.is_empty() {
+                for jump in fail_jumps {
+                    self.patch_jump(jump);
+                }
+                self.emit_op(Opcode::Pop);
+            }
+
+            if let Some(jump) = next_arm {
+                self.patch_jump(jump);
+            }
+        }
+    }
+
+    /// looks ahead for arms whose patterns are all int literals, with an
+    /// optional trailing `_`, that are close enough together for a jump table
+    fn int_arms(&self) -> Option<Vec<Option<i64>>> {
+        let mut scanner = self.scanner.clone();
+        let mut next = || loop {
+            match scanner.next() {
+                Some(tok) if matches!(tok.token_type, TokenType::Error(_)) => (),
+                tok => return tok.map(|t| t.token_type),
+            }
+        };
+
+        let mut token = Some(self.current.token_type.clone());
+        let mut arms = Vec::new();
+        loop {
+            while matches!(token, Some(TokenType::Newline | TokenType::Comma)) {
+                token = next();
+            }
+
+            let arm = match token? {
+                TokenType::RightBrace => break,
+                TokenType::Int(n) => Some(n),
+                TokenType::Minus => match next()? {
+                    TokenType::Int(n) => Some(-n),
+                    _ => return None,
+                },
+                TokenType::Identifier(name) if name == "_" => None,
+                _ => return None,
+            };
+
+            if next()? != TokenType::FatArrow || arms.contains(&None) {
+                return None;
+            }
+            arms.push(arm);
+
+            // skip the arm's expression
+            let mut depth = 0;
+            loop {
+                token = next();
+                match token.as_ref()? {
+                    TokenType::LeftParen | TokenType::LeftBracket | TokenType::LeftBrace => {
+                        depth += 1
+                    }
+                    TokenType::RightBrace if depth == 0 => break,
+                    TokenType::RightParen | TokenType::RightBracket | TokenType::RightBrace => {
+                        depth -= 1
+                    }
+                    TokenType::Newline | TokenType::Comma if depth == 0 => break,
+                    _ => (),
+                }
+            }
+        }
+
+        let ints: Vec<i64> = arms.iter().flatten().copied().collect();
+        let min = *ints.iter().min()?;
+        let max = *ints.iter().max()?;
+        let span = max as i128 - min as i128 + 1;
+        if ints.len() < 3 || span > u8::MAX as i128 || span > 2 * ints.len() as i128 {
+            return None;
+        }
+
+        Some(arms)
+    }
+
+    /// compiles arms of int literals into a table of jumps indexed by the value
+    fn match_table(&mut self, arms: Vec<Option<i64>>, end_jumps: &mut Vec<usize>) {
+        let ints: Vec<i64> = arms.iter().flatten().copied().collect();
+        let min = *ints.iter().min().unwrap();
+        let count = (*ints.iter().max().unwrap() - min + 1) as usize;
+
+        let constant = self.make_constant(Value::Int(min));
+        self.emit_bytes(Opcode::JumpTable as u8, constant as u8);
+        self.emit_byte(count as u8);
+
+        let table = self.chunk().code.len();
+        for _ in 0..=count {
+            self.emit_bytes(0xff, 0xff);
+        }
+        let table_end = self.chunk().code.len();
+
+        let mut targets = vec![None; count + 1];
+        for arm in arms {
+            self.skip_arm_separators();
+            self.pattern();
+            self.consume(TokenType::FatArrow, "Expect '=>' after pattern");
+
+            let entry = arm.map_or(count, |n| (n - min) as usize);
+            targets[entry].get_or_insert(self.chunk().code.len());
+
+            self.expression();
+            end_jumps.push(self.emit_jump(Opcode::Jump));
+        }
+        self.skip_arm_separators();
+
+        // values without an arm go to the default, or to the no match error
+        let default = targets[count].unwrap_or(self.chunk().code.len());
+        for (entry, target) in targets.into_iter().enumerate() {
+            let jump = target.unwrap_or(default) - table_end;
+            if jump > i16::MAX as usize {
+                self.error("Jump is out of bounds");
+            }
+
+            let code = &mut self.chunk().code;
+            code[table + 2 * entry] = ((jump >> 8) & 0xff) as u8;
+            code[table + 2 * entry + 1] = (jump & 0xff) as u8;
+        }
+    }
+
+    fn pattern(&mut self) -> Pattern {
+        match self.current.token_type.clone() {
+            TokenType::Identifier(name) => {
+                self.advance();
+                if name == "_" {
+                    Pattern::Wildcard
+                } else {
+                    Pattern::Binding(name)
+                }
+            }
+            TokenType::LeftBracket => {
+                self.advance();
+
+                let mut items = Vec::new();
+                while !self.end_flag && self.current.token_type != TokenType::RightBracket {
+                    items.push(self.pattern());
+                    if !self.matches(TokenType::Comma) {
+                        break;
+                    }
+                }
+
+                self.consume(TokenType::RightBracket, "Expect ']' after list pattern");
+                Pattern::List(items)
+            }
+            TokenType::LeftBrace => {
+                self.advance();
+
+                // `{key}` binds a variable named after the key, `{key: pattern}`
+                // matches the key's value against the pattern
+                let mut entries = Vec::new();
+                while !self.end_flag && self.current.token_type != TokenType::RightBrace {
+                    let key = match self.current.token_type.clone() {
+                        TokenType::Identifier(key) | TokenType::String(key) => key,
+                        _ => {
+                            self.error_at_current("Expect key in map pattern");
+                            break;
+                        }
+                    };
+                    self.advance();
+
+                    let pattern = if self.matches(TokenType::Colon) {
+                        self.pattern()
+                    } else {
+                        Pattern::Binding(key.clone())
+                    };
+                    entries.push((key, pattern));
+
+                    if !self.matches(TokenType::Comma) {
+                        break;
+                    }
+                }
+
+                self.consume(TokenType::RightBrace, "Expect '}' after map pattern");
+                Pattern::Map(entries)
+            }
+            _ => {
+                let low = self.literal_pattern();
+                if self.matches(TokenType::DotDot) {
+                    Pattern::Range(low, self.literal_pattern(), false)
+                } else if self.matches(TokenType::DotDotEqual) {
+                    Pattern::Range(low, self.literal_pattern(), true)
+                } else {
+                    Pattern::Literal(low)
+                }
+            }
+        }
+    }
+
+    fn literal_pattern(&mut self) -> Value {
+        let negative = self.matches(TokenType::Minus);
+
+        let value = match self.current.token_type.clone() {
+            TokenType::Int(n) if negative => Value::Int(-n),
+            TokenType::BigInt(n) if negative => Value::from(-&n),
+            TokenType::Number(x) if negative => Value::Number(-x),
+            TokenType::Int(n) => Value::Int(n),
+            TokenType::BigInt(n) => Value::BigInt(n),
+            TokenType::Number(x) => Value::Number(x),
+            TokenType::String(s) if !negative => Value::String(s),
+            TokenType::True if !negative => Value::Bool(true),
+            TokenType::False if !negative => Value::Bool(false),
+            TokenType::Nil if !negative => Value::Nil,
+            _ => {
+                self.error_at_current("Expect pattern");
+                return Value::Nil;
+            }
+        };
+
+        self.advance();
+        value
+    }
+
+    /// pushes the part of the matched value reached by following `path`
+    fn load_path(&mut self, subject: usize, path: &[Value]) {
+        self.emit_bytes(Opcode::GetLocal as u8, subject as u8);
+        for key in path {
+            self.emit_constant(key.clone());
+            self.emit_op(Opcode::GetIndex);
+        }
+    }
+
+    fn test_pattern(
+        &mut self,
+        pattern: &Pattern,
+        subject: usize,
+        path: &mut Vec<Value>,
+        fail_jumps: &mut Vec<usize>,
+    ) {
+        match pattern {
+            Pattern::Wildcard | Pattern::Binding(_) => return,
+            Pattern::Literal(value) => {
+                self.load_path(subject, path);
+                self.emit_constant(value.clone());
+                self.emit_op(Opcode::Equal);
+            }
+            Pattern::Range(low, high, inclusive) => {
+                self.load_path(subject, path);
+                self.emit_constant(low.clone());
+                self.emit_constant(high.clone());
+                self.emit_bytes(Opcode::InRange as u8, *inclusive as u8);
+            }
+            Pattern::List(items) => {
+                if items.len() > u8::MAX as usize {
+                    self.error("Too many elements in list pattern");
+                }
+
+                self.load_path(subject, path);
+                self.emit_bytes(Opcode::IsList as u8, items.len() as u8);
+            }
+            Pattern::Map(_) => {
+                self.load_path(subject, path);
+                self.emit_op(Opcode::IsMap);
+            }
+        }
+
+        fail_jumps.push(self.emit_jump(Opcode::JumpIfFalse));
+        self.emit_op(Opcode::Pop);
+
+        match pattern {
+            Pattern::List(items) => {
+                for (i, item) in items.iter().enumerate() {
+                    path.push(Value::Int(i as i64));
+                    self.test_pattern(item, subject, path, fail_jumps);
+                    path.pop();
+                }
+            }
+            Pattern::Map(entries) => {
+                for (key, item) in entries {
+                    self.load_path(subject, path);
+                    let constant = self.make_constant(Value::String(key.clone()));
+                    self.emit_bytes(Opcode::HasKey as u8, constant as u8);
+                    fail_jumps.push(self.emit_jump(Opcode::JumpIfFalse));
+                    self.emit_op(Opcode::Pop);
+
+                    path.push(Value::String(key.clone()));
+                    self.test_pattern(item, subject, path, fail_jumps);
+                    path.pop();
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// declares a local for each variable in a matched pattern, returning how many
+    fn bind_pattern(&mut self, pattern: &Pattern, subject: usize, path: &mut Vec<Value>) -> usize {
+        match pattern {
+            Pattern::Binding(name) => {
+                self.load_path(subject, path);
+                self.add_local(name.clone());
+                1
+            }
+            Pattern::List(items) => {
+                let mut count = 0;
+                for (i, item) in items.iter().enumerate() {
+                    path.push(Value::Int(i as i64));
+                    count += self.bind_pattern(item, subject, path);
+                    path.pop();
+                }
+                count
+            }
+            Pattern::Map(entries) => {
+                let mut count = 0;
+                for (key, item) in entries {
+                    path.push(Value::String(key.clone()));
+                    count += self.bind_pattern(item, subject, path);
+                    path.pop();
+                }
+                count
+            }
+            _ => 0,
+        }
+    }
+
     pub fn call(&mut self, _can_assign: bool) {
-        let mut num_params = 0;
+        let num_args = self.arguments();
+        self.emit_op(Opcode::Call);
+        self.emit_byte(num_args);
+    }
+
+    fn arguments(&mut self) -> u8 {
+        let mut num_args = 0;
 
         if self.current.token_type.clone() != TokenType::RightParen {
             while {
-                num_params += 1;
+                num_args += 1;
                 self.expression();
 
                 self.matches(TokenType::Comma)
@@ -328,22 +1447,44 @@ impl Parser {
         }
 
         self.consume(TokenType::RightParen, "Expected ) after arguments");
+        num_args
+    }
 
-        self.emit_op(Opcode::Call);
-        self.emit_byte(num_params);
+    /// postfix `?` unwraps an `Ok`, or returns an `Err` from the function.
+    /// Outside any function there's nothing to return to, so an `Err` is an error
+    pub fn propagate(&mut self, _can_assign: bool) {
+        if let FunctionType::Script = self.compiler().function.function_type {
+            self.emit_op(Opcode::UnwrapOk);
+            return;
+        }
+
+        self.emit_op(Opcode::IsErr);
+        let ok_jump = self.emit_jump(Opcode::JumpIfFalse);
+        self.emit_op(Opcode::Pop);
+        self.emit_op(Opcode::Return);
+
+        self.patch_jump(ok_jump);
+        self.emit_op(Opcode::Pop);
+        self.emit_op(Opcode::UnwrapOk);
     }
 
     pub fn binary(&mut self, _can_assign: bool) {
         let operator = self.previous.token_type.clone();
         let rule = operator.rule();
-        let precedence = Precedence::from(rule.precedence as usize + 1);
+        // `**` is right associative, so its right operand may hold another `**`
+        let precedence = match operator {
+            TokenType::StarStar => rule.precedence,
+            _ => Precedence::from(rule.precedence as usize + 1),
+        };
         self.parse_precedence(precedence);
 
         match operator {
             TokenType::Plus => self.emit_op(Opcode::Add),
             TokenType::Minus => self.emit_op(Opcode::Subtract),
             TokenType::Star => self.emit_op(Opcode::Multiply),
+            TokenType::StarStar => self.emit_op(Opcode::Power),
             TokenType::Slash => self.emit_op(Opcode::Divide),
+            TokenType::SlashSlash => self.emit_op(Opcode::IntDivide),
             TokenType::Mod => self.emit_op(Opcode::Mod),
             TokenType::BangEqual => self.emit_ops(Opcode::Equal, Opcode::Not),
             TokenType::EqualEqual => self.emit_op(Opcode::Equal),
@@ -353,6 +1494,9 @@ impl Parser {
             TokenType::LessEqual => self.emit_ops(Opcode::Greater, Opcode::Not),
             TokenType::BitwiseAnd => self.emit_op(Opcode::BitwiseAnd),
             TokenType::BitwiseOr => self.emit_op(Opcode::BitwiseOr),
+            TokenType::BitwiseXor => self.emit_op(Opcode::BitwiseXor),
+            TokenType::ShiftLeft => self.emit_op(Opcode::ShiftLeft),
+            TokenType::ShiftRight => self.emit_op(Opcode::ShiftRight),
             TokenType::LogicalAnd => self.emit_op(Opcode::LogicalAnd),
             TokenType::LogicalOr => self.emit_op(Opcode::LogicalOr),
             TokenType::And => self.emit_op(Opcode::LogicalAnd),
@@ -367,21 +1511,27 @@ impl Parser {
         if let Some(prefix_rule) = rule.prefix {
             let can_assign = precedence as usize <= Precedence::Assignment as usize;
             prefix_rule(self, can_assign);
-
-            let prec_u8 = precedence as u8;
-            while prec_u8 <= self.current.token_type.rule().precedence as u8 {
-                self.advance();
-                if let Some(infix_rule) = self.previous.token_type.rule().infix {
-                    infix_rule(self, can_assign);
-                }
-            }
-
+            self.parse_infix(precedence);
             return;
         }
 
         self.error("Expected expression");
     }
 
+    /// parses infix operators binding at least as tightly as `precedence`
+    /// after an operand has been compiled
+    fn parse_infix(&mut self, precedence: Precedence) {
+        let can_assign = precedence as usize <= Precedence::Assignment as usize;
+
+        let prec_u8 = precedence as u8;
+        while prec_u8 <= self.current.token_type.rule().precedence as u8 {
+            self.advance();
+            if let Some(infix_rule) = self.previous.token_type.rule().infix {
+                infix_rule(self, can_assign);
+            }
+        }
+    }
+
     pub fn literal(&mut self, _can_assign: bool) {
         let token_type = self.previous.token_type.clone();
         match token_type {
@@ -407,85 +1557,221 @@ impl Parser {
     }
 
     pub fn variable(&mut self, can_assign: bool) {
-        let identifier = self.previous.clone();
-        let name = match identifier.token_type.clone() {
+        let name = match self.previous.token_type.clone() {
             TokenType::Identifier(name) => name,
             _ => unreachable!("In variable() without name"),
         };
 
-        let (get_op, set_op, constant) = match self.resolve_local(&identifier) {
-            Ok(id) => (Opcode::GetLocal, Opcode::SetLocal, id),
-            Err(_) => (
-                Opcode::GetGlobal,
-                Opcode::SetGlobal,
-                self.make_constant(Value::String(name)),
-            ),
-        };
+        let (get_op, set_op, constant) = self.resolve_variable(name);
 
         if can_assign && self.matches(TokenType::Equal) {
             self.expression();
             self.emit_op(set_op);
             self.emit_byte(constant as u8);
+        } else if let Some(op) = self.compound_assignment(can_assign) {
+            self.emit_bytes(get_op as u8, constant as u8);
+            self.expression();
+            self.emit_op(op);
+            self.emit_bytes(set_op as u8, constant as u8);
         } else {
             self.emit_op(get_op);
             self.emit_byte(constant as u8);
         }
     }
 
+    /// the get and set opcodes for a name along with their operand
+    fn resolve_variable(&mut self, name: String) -> (Opcode, Opcode, usize) {
+        match self.compiler().resolve_local(&name) {
+            Some(id) => (Opcode::GetLocal, Opcode::SetLocal, id),
+            None => match self.resolve_upvalue(&name) {
+                Some(id) => (Opcode::GetUpvalue, Opcode::SetUpvalue, id),
+                None => (
+                    Opcode::GetGlobal,
+                    Opcode::SetGlobal,
+                    self.make_constant(Value::String(name)),
+                ),
+            },
+        }
+    }
+
+    /// looks ahead for `a, b = ...`, `[x, y] = ...` or `{name} = ...`
+    fn is_destructuring(&self) -> bool {
+        let starts_group = match self.current.token_type {
+            TokenType::LeftBracket | TokenType::LeftBrace => true,
+            TokenType::Identifier(_) => false,
+            _ => return false,
+        };
+
+        let mut scanner = self.scanner.clone();
+        let mut token = Some(self.current.token_type.clone());
+        let mut depth = 0;
+        let mut has_comma = false;
+
+        while let Some(token_type) = token {
+            match token_type {
+                TokenType::LeftBracket | TokenType::LeftBrace => depth += 1,
+                TokenType::RightBracket | TokenType::RightBrace if depth > 0 => depth -= 1,
+                TokenType::Comma => has_comma |= depth == 0,
+                TokenType::Identifier(_) | TokenType::String(_) | TokenType::Colon => (),
+                TokenType::Equal if depth == 0 => return starts_group || has_comma,
+                _ => return false,
+            }
+
+            token = scanner.next().map(|t| t.token_type);
+        }
+
+        false
+    }
+
+    /// assigns the parts of a list or map to the variables named in a pattern,
+    /// after evaluating the whole right hand side
+    fn destructuring_assignment(&mut self) {
+        let mut targets = vec![self.pattern()];
+        while self.matches(TokenType::Comma) {
+            targets.push(self.pattern());
+        }
+        let target = if targets.len() == 1 {
+            targets.pop().unwrap()
+        } else {
+            Pattern::List(targets)
+        };
+
+        self.consume(TokenType::Equal, "Expect '=' after assignment targets");
+
+        self.begin_scope();
+        self.expression();
+
+        // `a, b = b, a` packs the values into a list first
+        let mut count = 1;
+        while self.matches(TokenType::Comma) {
+            self.expression();
+            count += 1;
+        }
+        if count > 1 {
+            if count > u8::MAX as usize {
+                self.error("Too many values in assignment");
+            }
+            self.emit_bytes(Opcode::BuildList as u8, count as u8);
+        }
+
+        let subject = self.compiler().locals.len();
+        self.add_local(String::new());
+        self.assign_pattern(&target, subject, &mut Vec::new());
+
+        let compiler = self.compiler();
+        compiler.scope_depth -= 1;
+        compiler.locals.pop();
+        self.emit_op(Opcode::Pop);
+    }
+
+    fn assign_pattern(&mut self, pattern: &Pattern, subject: usize, path: &mut Vec<Value>) {
+        match pattern {
+            Pattern::Wildcard => (),
+            Pattern::Binding(name) => {
+                let (_, set_op, arg) = self.resolve_variable(name.clone());
+                self.load_path(subject, path);
+                self.emit_bytes(set_op as u8, arg as u8);
+                self.emit_op(Opcode::Pop);
+            }
+            Pattern::List(items) => {
+                if items.len() > u8::MAX as usize {
+                    self.error("Too many assignment targets");
+                }
+
+                self.load_path(subject, path);
+                self.emit_bytes(Opcode::ExpectList as u8, items.len() as u8);
+                self.emit_op(Opcode::Pop);
+
+                for (i, item) in items.iter().enumerate() {
+                    path.push(Value::Int(i as i64));
+                    self.assign_pattern(item, subject, path);
+                    path.pop();
+                }
+            }
+            Pattern::Map(entries) => {
+                for (key, item) in entries {
+                    self.load_path(subject, path);
+                    let constant = self.make_constant(Value::String(key.clone()));
+                    self.emit_bytes(Opcode::ExpectKey as u8, constant as u8);
+                    self.emit_op(Opcode::Pop);
+
+                    path.push(Value::String(key.clone()));
+                    self.assign_pattern(item, subject, path);
+                    path.pop();
+                }
+            }
+            Pattern::Literal(_) | Pattern::Range(..) => self.error("Invalid assignment target"),
+        }
+    }
+
     fn add_local(&mut self, name: String) {
-        // no more than 255 local variables
-        if self.local_count == u8::MAX as usize {
+        // no more than 256 local variables, including the callee
+        if self.compiler().locals.len() > u8::MAX as usize {
             self.error("Too many local variables");
             return;
         }
 
+        let compiler = self.compiler();
         let local = Local {
             name,
-            depth: self.scope_depth,
+            depth: compiler.scope_depth,
         };
 
-        self.local_count += 1;
-        self.locals.push(local);
+        compiler.locals.push(local);
     }
 
     fn begin_scope(&mut self) {
-        self.scope_depth += 1;
+        self.compiler().scope_depth += 1;
     }
 
+    /// ends a block's scope, popping its locals from beneath the block's value
     fn end_scope(&mut self) {
-        self.scope_depth -= 1;
-
-        // pop local variables introduced in this scope off  the stack
-        while self.local_count > 0 && self.locals[self.local_count - 1].depth > self.scope_depth {
-            self.emit_op(Opcode::Pop);
-            self.local_count -= 1;
+        let compiler = self.compiler();
+        compiler.scope_depth -= 1;
+
+        let depth = compiler.scope_depth;
+        let count = compiler
+            .locals
+            .iter()
+            .rev()
+            .take_while(|local| local.depth > depth)
+            .count();
+        compiler.locals.truncate(compiler.locals.len() - count);
+
+        if count > 0 {
+            self.emit_op(Opcode::PopLocals);
+            self.emit_byte(count as u8);
         }
     }
 
-    fn resolve_local(&self, name: &Token) -> Result<usize, ()> {
-        if self.locals.is_empty() {
-            return Err(());
+    /// finds a local of an enclosing function and captures it, threading the
+    /// capture through every function in between
+    fn resolve_upvalue(&mut self, name: &str) -> Option<usize> {
+        let current = self.compilers.len() - 1;
+        let (level, slot) = self.compilers[..current]
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(i, compiler)| compiler.resolve_local(name).map(|slot| (i, slot)))?;
+
+        let mut index = slot;
+        let mut is_local = true;
+        for compiler in &mut self.compilers[level + 1..] {
+            index = compiler.add_upvalue(UpvalueRef { index, is_local });
+            is_local = false;
         }
 
-        let mut local_count = self.local_count - 1;
-        let identifier = match &name.token_type {
-            TokenType::Identifier(id) => id,
-            _ => unreachable!("Was not given an identifier to resolve_local"),
-        };
-
-        loop {
-            let local = &self.locals[local_count];
-            if local.name == *identifier {
-                return Ok(local_count);
-            }
-
-            if local_count == 0 {
-                break;
-            }
-
-            local_count -= 1;
+        if index > u8::MAX as usize {
+            self.error("Too many captured variables in function");
         }
 
-        Err(())
+        Some(index)
     }
 }
+
+/// whether a string could be written as a variable name
+pub fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c == '_' || c.is_alphabetic())
+        && chars.all(|c| c == '_' || c.is_alphanumeric())
+}
@@ -6,9 +6,11 @@ pub enum Precedence {
     And,        // &&
     Equality,   // == !=
     Comparison, // < > <= >=
-    Term,       // + -
-    Factor,     // * /
+    Shift,      // << >>
+    Term,       // + - & | ^
+    Factor,     // * / // %
     Unary,      // ! -
+    Exponent,   // **
     Call,       // . ()
     Primary,
 }
@@ -22,11 +24,13 @@ impl Precedence {
             3 => Precedence::And,
             4 => Precedence::Equality,
             5 => Precedence::Comparison,
-            6 => Precedence::Term,
-            7 => Precedence::Factor,
-            8 => Precedence::Unary,
-            9 => Precedence::Call,
-            10 => Precedence::Primary,
+            6 => Precedence::Shift,
+            7 => Precedence::Term,
+            8 => Precedence::Factor,
+            9 => Precedence::Unary,
+            10 => Precedence::Exponent,
+            11 => Precedence::Call,
+            12 => Precedence::Primary,
             _ => Precedence::None,
         }
     }
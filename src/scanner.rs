@@ -1,27 +1,90 @@
+use crate::bigint::BigInt;
 use crate::token::{Token, TokenType};
 
+use std::rc::Rc;
+use unicode_xid::UnicodeXID;
+
 fn is_digit(c: char) -> bool {
-    matches!(c, '0'..='9')
+    c.is_ascii_digit()
+}
+
+/// identifiers follow unicode's XID rules, with `_` allowed as a start character
+fn is_ident_start(c: char) -> bool {
+    c == '_' || c.is_xid_start()
+}
+
+fn is_ident_continue(c: char) -> bool {
+    c.is_xid_continue()
+}
+
+/// `_` separators may only appear between two digits
+fn misplaced_underscore(digits: &str) -> bool {
+    digits.starts_with('_') || digits.ends_with('_') || digits.contains("__")
 }
 
-fn is_alpha(c: char) -> bool {
-    matches!(c, 'a'..='z' | 'A'..='Z' | '_')
+/// a string literal suspended while an interpolated expression is scanned
+#[derive(Clone)]
+struct Interpolation {
+    delimiter: char,
+    multiline: Option<Multiline>,
+    depth: usize, // unmatched '{' inside the expression
 }
 
+/// layout of a triple quoted string, found before its contents are scanned
+#[derive(Clone)]
+struct Multiline {
+    content_end: usize, // index of the end of the string's contents
+    closing: usize,     // index of the closing delimiter
+    indent: String,     // indentation stripped from each line
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum CommentKind {
+    Line,  // # ...
+    Block, // #[ ... ]#, may be nested
+    Doc,   // ## ...
+}
+
+/// comments skipped between tokens, kept so tooling can recover them
+#[derive(Debug, PartialEq, Clone)]
+pub struct Trivia {
+    pub kind: CommentKind,
+    pub text: String,
+    pub line: usize,
+}
+
+/// scans source code one unicode scalar value at a time. All positions,
+/// lengths and columns count characters, not bytes. Cloning is cheap enough
+/// for the parser to look ahead with a copy.
+#[derive(Clone)]
 pub struct Scanner {
-    source: String,
+    source: Rc<[char]>,
     start: usize, // index of beginning of lexeme being scanned
     pos: usize,   // current character being looked at
     line: usize,
+    line_start: usize, // index of the first character on the current line
+    start_line: usize, // line and column the lexeme being scanned begins at
+    start_col: usize,
+    interpolations: Vec<Interpolation>,
+    trivia: Vec<Trivia>,
+    brackets: Vec<char>, // unclosed brackets, newlines only matter directly inside '{'
+    ends_statement: bool, // whether the last token can end a statement
 }
 
 impl Scanner {
     pub fn new(source: String) -> Scanner {
         Scanner {
-            source,
+            source: source.chars().collect(),
             start: 0,
             pos: 0,
             line: 1,
+            line_start: 0,
+            start_line: 1,
+            start_col: 0,
+            interpolations: Vec::new(),
+            trivia: Vec::new(),
+            brackets: Vec::new(),
+            ends_statement: false,
         }
     }
 
@@ -36,10 +99,39 @@ impl Scanner {
     }
 
     pub fn next(&mut self) -> Option<Token> {
-        // ignores whitespace between tokens
-        self.skip_whitespace();
+        // ignores whitespace and comments between tokens
+        let tok = match self.skip_whitespace() {
+            Ok(Some(newline)) => Some(newline),
+            Ok(None) => self.scan_token(),
+            Err(tok) => Some(tok),
+        };
 
-        self.start = self.pos;
+        if let Some(tok) = &tok {
+            self.ends_statement = matches!(
+                tok.token_type,
+                TokenType::Identifier(_)
+                    | TokenType::String(_)
+                    | TokenType::Number(_)
+                    | TokenType::Int(_)
+                    | TokenType::BigInt(_)
+                    | TokenType::True
+                    | TokenType::False
+                    | TokenType::Nil
+                    | TokenType::This
+                    | TokenType::Super
+                    | TokenType::Return
+                    | TokenType::RightParen
+                    | TokenType::RightBrace
+                    | TokenType::RightBracket
+                    | TokenType::Question
+            );
+        }
+
+        tok
+    }
+
+    fn scan_token(&mut self) -> Option<Token> {
+        self.mark_start();
 
         if self.eof() {
             return None;
@@ -47,21 +139,115 @@ impl Scanner {
 
         let c = self.advance();
         match c {
-            '(' => Some(self.make_token(TokenType::LeftParen)),
-            ')' => Some(self.make_token(TokenType::RightParen)),
-            '{' => Some(self.make_token(TokenType::LeftBrace)),
-            '}' => Some(self.make_token(TokenType::RightBrace)),
+            '(' => {
+                self.brackets.push('(');
+                Some(self.make_token(TokenType::LeftParen))
+            }
+            ')' => {
+                self.close_bracket('(');
+                Some(self.make_token(TokenType::RightParen))
+            }
+            '{' => {
+                if let Some(interpolation) = self.interpolations.last_mut() {
+                    interpolation.depth += 1;
+                }
+                self.brackets.push('{');
+                Some(self.make_token(TokenType::LeftBrace))
+            }
+            '}' => match self.interpolations.last_mut() {
+                Some(interpolation) if interpolation.depth == 0 => {
+                    // end of an interpolated expression, resume its string
+                    let interpolation = self.interpolations.pop().unwrap();
+                    self.close_bracket('"');
+                    Some(self.string_segment(
+                        interpolation.delimiter,
+                        interpolation.multiline,
+                        false,
+                        false,
+                    ))
+                }
+                Some(interpolation) => {
+                    interpolation.depth -= 1;
+                    self.close_bracket('{');
+                    Some(self.make_token(TokenType::RightBrace))
+                }
+                None => {
+                    self.close_bracket('{');
+                    Some(self.make_token(TokenType::RightBrace))
+                }
+            },
+            '[' => {
+                self.brackets.push('[');
+                Some(self.make_token(TokenType::LeftBracket))
+            }
+            ']' => {
+                self.close_bracket('[');
+                Some(self.make_token(TokenType::RightBracket))
+            }
             ';' => Some(self.make_token(TokenType::Semicolon)),
+            '?' => Some(self.make_token(TokenType::Question)),
+            ':' => Some(self.make_token(TokenType::Colon)),
             ',' => Some(self.make_token(TokenType::Comma)),
-            '.' => Some(self.make_token(TokenType::Dot)),
-            '-' => Some(self.make_token(TokenType::Minus)),
-            '+' => Some(self.make_token(TokenType::Plus)),
-            '/' => Some(self.make_token(TokenType::Slash)),
-            '*' => Some(self.make_token(TokenType::Star)),
-            '%' => Some(self.make_token(TokenType::Mod)),
+            '.' => {
+                let token_type = if !self.matches('.') {
+                    TokenType::Dot
+                } else if self.matches('=') {
+                    TokenType::DotDotEqual
+                } else {
+                    TokenType::DotDot
+                };
+                Some(self.make_token(token_type))
+            }
+            '-' => {
+                let token_type = if self.matches('=') {
+                    TokenType::MinusEqual
+                } else {
+                    TokenType::Minus
+                };
+                Some(self.make_token(token_type))
+            }
+            '+' => {
+                let token_type = if self.matches('=') {
+                    TokenType::PlusEqual
+                } else {
+                    TokenType::Plus
+                };
+                Some(self.make_token(token_type))
+            }
+            '/' => {
+                let token_type = if self.matches('/') {
+                    TokenType::SlashSlash
+                } else if self.matches('=') {
+                    TokenType::SlashEqual
+                } else {
+                    TokenType::Slash
+                };
+                Some(self.make_token(token_type))
+            }
+            '*' => {
+                let token_type = if self.matches('=') {
+                    TokenType::StarEqual
+                } else if self.matches('*') {
+                    TokenType::StarStar
+                } else {
+                    TokenType::Star
+                };
+                Some(self.make_token(token_type))
+            }
+            '%' => {
+                let token_type = if self.matches('=') {
+                    TokenType::ModEqual
+                } else {
+                    TokenType::Mod
+                };
+                Some(self.make_token(token_type))
+            }
+            '^' => Some(self.make_token(TokenType::BitwiseXor)),
             '&' => {
                 let token_type = if self.matches('&') {
                     TokenType::LogicalAnd
+                } else if self.matches('=') {
+                    TokenType::AmpEqual
                 } else {
                     TokenType::BitwiseAnd
                 };
@@ -70,6 +256,8 @@ impl Scanner {
             '|' => {
                 let token_type = if self.matches('|') {
                     TokenType::LogicalOr
+                } else if self.matches('=') {
+                    TokenType::PipeEqual
                 } else {
                     TokenType::BitwiseOr
                 };
@@ -86,6 +274,8 @@ impl Scanner {
             '=' => {
                 let token_type = if self.matches('=') {
                     TokenType::EqualEqual
+                } else if self.matches('>') {
+                    TokenType::FatArrow
                 } else {
                     TokenType::Equal
                 };
@@ -94,6 +284,8 @@ impl Scanner {
             '<' => {
                 let token_type = if self.matches('=') {
                     TokenType::LessEqual
+                } else if self.matches('<') {
+                    TokenType::ShiftLeft
                 } else {
                     TokenType::Less
                 };
@@ -102,45 +294,127 @@ impl Scanner {
             '>' => {
                 let token_type = if self.matches('=') {
                     TokenType::GreaterEqual
+                } else if self.matches('>') {
+                    TokenType::ShiftRight
                 } else {
                     TokenType::Greater
                 };
                 Some(self.make_token(token_type))
             }
-            '\'' | '\"' => Some(self.string(c)),
-            '0'..='9' => Some(self.number()),
-            'a'..='z' | 'A'..='Z' | '_' => Some(self.identifier()),
-            _ => None,
+            '\'' | '\"' => Some(self.string(c, false)),
+            'r' if matches!(self.peek(), '\'' | '\"') => {
+                let delimiter = self.advance();
+                Some(self.string(delimiter, true))
+            }
+            '0'..='9' => Some(self.number(c)),
+            c if is_ident_start(c) => Some(self.identifier()),
+            _ => Some(self.error(&format!("Unexpected character '{}'", c))),
         }
     }
 
+    /// comments skipped so far, in source order
+    #[cfg(test)]
+    pub fn trivia(&self) -> &[Trivia] {
+        &self.trivia
+    }
+
+    /// joins the doc comment lines directly above the given line
+    pub fn doc_comment(&self, line: usize) -> Option<String> {
+        let mut lines = Vec::new();
+        let mut expected_line = line;
+        for trivia in self.trivia.iter().rev() {
+            if trivia.line >= line {
+                continue;
+            }
+
+            if trivia.kind != CommentKind::Doc || trivia.line + 1 != expected_line {
+                break;
+            }
+
+            lines.push(trivia.text.as_str());
+            expected_line = trivia.line;
+        }
+
+        if lines.is_empty() {
+            return None;
+        }
+
+        lines.reverse();
+        Some(lines.join("\n"))
+    }
+
+    fn close_bracket(&mut self, open: char) {
+        if self.brackets.last() == Some(&open) {
+            self.brackets.pop();
+        }
+    }
+
+    /// a line break ends a statement unless the statement can't end there, e.g.
+    /// after an operator or inside parentheses
+    fn newline_is_significant(&self) -> bool {
+        self.ends_statement && matches!(self.brackets.last(), None | Some('{'))
+    }
+
+    /// a line starting with '.', `else`, `catch` or `finally` continues the
+    /// statement before it
+    fn continues_statement(&self) -> bool {
+        let rest = &self.source[self.pos..];
+        if rest.first() == Some(&'.') {
+            return true;
+        }
+
+        ["else", "catch", "finally"].iter().any(|keyword| {
+            let len = keyword.len();
+            rest.len() >= len
+                && rest[..len].iter().copied().eq(keyword.chars())
+                && !rest.get(len).is_some_and(|c| is_ident_continue(*c))
+        })
+    }
+
+    fn mark_start(&mut self) {
+        self.start = self.pos;
+        self.start_line = self.line;
+        self.start_col = self.column();
+    }
+
     fn make_token(&self, token_type: TokenType) -> Token {
         let len = self.pos - self.start;
-        Token::new(token_type, self.line, self.start, len)
+        Token::new(token_type, self.start_line, self.start_col, len)
     }
 
-    /// emits a syntax error token
+    /// emits a syntax error token spanning the lexeme being scanned
     fn error(&self, msg: &str) -> Token {
-        Token::new(
-            TokenType::Error(String::from(msg)),
-            self.line,
-            self.start,
-            msg.len(),
-        )
+        let len = self.pos - self.start;
+        self.error_at(msg, self.start_line, self.start_col, len)
+    }
+
+    /// emits a syntax error token at a position inside the lexeme
+    fn error_at(&self, msg: &str, line: usize, col: usize, len: usize) -> Token {
+        Token::new(TokenType::Error(String::from(msg)), line, col, len)
+    }
+
+    fn column(&self) -> usize {
+        self.pos - self.line_start
+    }
+
+    fn lexeme(&self, start: usize, end: usize) -> String {
+        self.source[start..end].iter().collect()
     }
 
     fn peek(&self) -> char {
-        if self.eof() {
-            '\0'
-        } else {
-            self.source[self.pos..].chars().next().unwrap()
-        }
+        self.source.get(self.pos).copied().unwrap_or('\0')
     }
 
     /// advances the scanner's position and returns the consumed character
     fn advance(&mut self) -> char {
         let c = self.peek();
         self.pos += 1;
+
+        if c == '\n' {
+            self.line += 1;
+            self.line_start = self.pos;
+        }
+
         c
     }
 
@@ -149,54 +423,337 @@ impl Scanner {
         if self.eof() || self.peek() != expected {
             false
         } else {
-            self.pos += 1;
+            self.advance();
             true
         }
     }
 
-    fn string(&mut self, delimiter: char) -> Token {
-        assert!(delimiter == '\'' || delimiter == '\"');
-        self.advance();
-        while self.peek() != delimiter && !self.eof() {
-            if self.peek() == '\n' {
-                self.line += 1;
+    /// scans a string after its opening delimiter, raw strings skip escape
+    /// processing and interpolation
+    fn string(&mut self, delimiter: char, raw: bool) -> Token {
+        if self.peek() == delimiter && self.peek_next() == delimiter {
+            // consume the rest of the opening triple quote
+            self.advance();
+            self.advance();
+            return self.multiline_string(delimiter, raw);
+        }
+
+        self.string_segment(delimiter, None, raw, false)
+    }
+
+    /// scans a triple quoted string. When the closing delimiter is on its own
+    /// line, its indentation is stripped from every line of the string.
+    fn multiline_string(&mut self, delimiter: char, raw: bool) -> Token {
+        let is_closing = |s: &Scanner| {
+            s.peek() == delimiter
+                && s.peek_next() == delimiter
+                && s.source.get(s.pos + 2) == Some(&delimiter)
+        };
+
+        // a line break directly after the opening delimiter isn't part of the string
+        let rest_of_line = &self.source[self.pos..];
+        if let Some(newline) = rest_of_line.iter().position(|&c| c == '\n') {
+            if rest_of_line[..newline].iter().all(|c| c.is_whitespace()) {
+                while self.advance() != '\n' {}
             }
+        }
 
-            self.advance();
+        // find the closing delimiter, skipping over interpolated expressions
+        let content_start = (self.pos, self.line, self.line_start);
+        let mut depth = 0;
+        while !(self.eof() || depth == 0 && is_closing(self)) {
+            let c = self.advance();
+            if raw {
+                continue;
+            }
+
+            match c {
+                '\\' => {
+                    self.advance();
+                }
+                '{' => depth += 1,
+                '}' if depth > 0 => depth -= 1,
+                '\'' | '"' if depth > 0 => {
+                    while !self.eof() && self.peek() != c {
+                        if self.advance() == '\\' {
+                            self.advance();
+                        }
+                    }
+                    self.advance();
+                }
+                _ => (),
+            }
         }
 
         if self.eof() {
-            self.error("Unterminated string")
-        } else {
-            // consume closing '
-            self.advance();
+            return self.error("Unterminated string");
+        }
+
+        let content = &self.source[content_start.0..self.pos];
+        let (content_end, indent) = match content.iter().rposition(|&c| c == '\n') {
+            Some(i) if content[i + 1..].iter().all(|&c| c == ' ' || c == '\t') => {
+                (content_start.0 + i, content[i + 1..].iter().collect())
+            }
+            _ => (self.pos, String::new()),
+        };
+        let multiline = Multiline {
+            content_end,
+            closing: self.pos,
+            indent,
+        };
+
+        (self.pos, self.line, self.line_start) = content_start;
+        let at_line_start = self.pos == self.line_start;
+        self.string_segment(delimiter, Some(multiline), raw, at_line_start)
+    }
+
+    /// scans the contents of a string up to its closing delimiter or the next
+    /// interpolated expression
+    fn string_segment(
+        &mut self,
+        delimiter: char,
+        mut multiline: Option<Multiline>,
+        raw: bool,
+        mut at_line_start: bool,
+    ) -> Token {
+        let mut value = String::new();
+        let mut error = None;
+        loop {
+            match &mut multiline {
+                Some(ml) if self.pos >= ml.content_end => {
+                    while self.pos < ml.closing + 3 {
+                        self.advance();
+                    }
+                    break;
+                }
+                Some(ml) if at_line_start => {
+                    // strip indentation from the start of each line
+                    let line = &self.source[self.pos..ml.content_end];
+                    let stripped = line
+                        .iter()
+                        .zip(ml.indent.chars())
+                        .take_while(|(&a, b)| a == *b)
+                        .count();
+                    self.pos += stripped;
+                    at_line_start = false;
+                    continue;
+                }
+                Some(_) => (),
+                None if self.eof() => return self.error("Unterminated string"),
+                None if self.peek() == delimiter => {
+                    // consume closing delimiter
+                    self.advance();
+                    break;
+                }
+                None => (),
+            }
+
+            if raw {
+                let c = self.advance();
+                at_line_start = c == '\n';
+                value.push(c);
+                continue;
+            }
+
+            match self.peek() {
+                '\\' => match self.escape() {
+                    Ok(c) => value.push(c),
+                    Err(tok) => error = error.or(Some(tok)),
+                },
+                '{' => {
+                    // suspend the string until the expression's closing brace
+                    self.advance();
+                    self.brackets.push('"');
+                    self.interpolations.push(Interpolation {
+                        delimiter,
+                        multiline,
+                        depth: 0,
+                    });
 
-            let string = &self.source[self.start + 1..self.pos - 1];
-            self.make_token(TokenType::String(String::from(string)))
+                    return match error {
+                        Some(tok) => tok,
+                        None => self.make_token(TokenType::Interpolation(value)),
+                    };
+                }
+                _ => {
+                    let c = self.advance();
+                    at_line_start = c == '\n';
+                    value.push(c);
+                }
+            }
+
+            // an escape can't run past the end of a multi-line string's content
+            if let Some(ml) = &mut multiline {
+                ml.content_end = ml.content_end.max(self.pos);
+            }
+        }
+
+        match error {
+            Some(tok) => tok,
+            None => self.make_token(TokenType::String(value)),
         }
     }
 
-    fn number(&mut self) -> Token {
-        while is_digit(self.peek()) {
-            self.advance();
+    /// processes an escape sequence starting at a backslash
+    fn escape(&mut self) -> Result<char, Token> {
+        let (line, col, start) = (self.line, self.column(), self.pos);
+
+        // consume the '\'
+        self.advance();
+
+        let c = self.advance();
+        let escaped = match c {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            '\\' => '\\',
+            '\'' => '\'',
+            '"' => '"',
+            '{' => '{',
+            '}' => '}',
+            'u' => {
+                let mut digits = String::new();
+                if self.peek() == '{' {
+                    self.advance();
+                    while self.peek().is_ascii_hexdigit() && digits.len() < 6 {
+                        digits.push(self.advance());
+                    }
+                }
+
+                let value = u32::from_str_radix(&digits, 16).ok();
+                match value.and_then(char::from_u32) {
+                    Some(c) if self.matches('}') => c,
+                    _ => {
+                        let len = self.pos - start;
+                        return Err(self.error_at("Invalid unicode escape", line, col, len));
+                    }
+                }
+            }
+            _ => {
+                let msg = format!("Invalid escape sequence '\\{}'", c);
+                return Err(self.error_at(&msg, line, col, self.pos - start));
+            }
+        };
+
+        Ok(escaped)
+    }
+
+    fn number(&mut self, first: char) -> Token {
+        if first == '0' {
+            let radix = match self.peek() {
+                'x' | 'X' => Some((16, "hex")),
+                'o' | 'O' => Some((8, "octal")),
+                'b' | 'B' => Some((2, "binary")),
+                _ => None,
+            };
+
+            if let Some((radix, name)) = radix {
+                // consume the radix prefix
+                self.advance();
+                return self.radix_number(radix, name);
+            }
         }
 
+        self.digits(10);
+
+        let mut is_float = false;
         if self.peek() == '.' && is_digit(self.peek_next()) {
+            is_float = true;
+
             // consume the '.'
             self.advance();
+            self.digits(10);
+        }
+
+        if matches!(self.peek(), 'e' | 'E') {
+            is_float = true;
+            self.advance();
 
-            while is_digit(self.peek()) {
+            if matches!(self.peek(), '+' | '-') {
                 self.advance();
             }
+
+            if !is_digit(self.peek()) {
+                self.skip_alphanumeric();
+                return self.error("Missing digits in exponent of number literal");
+            }
+
+            self.digits(10);
+        }
+
+        if is_ident_continue(self.peek()) {
+            self.skip_alphanumeric();
+            return self.error("Invalid character in number literal");
+        }
+
+        let lexeme = self.lexeme(self.start, self.pos);
+        if lexeme
+            .split(['.', 'e', 'E', '+', '-'])
+            .any(misplaced_underscore)
+        {
+            return self.error("Misplaced '_' in number literal");
+        }
+
+        let digits: String = lexeme.chars().filter(|&c| c != '_').collect();
+        if is_float {
+            let num: f64 = digits.parse().unwrap();
+            return self.make_token(TokenType::Number(num));
+        }
+
+        self.int_token(&digits, 10)
+    }
+
+    /// scans the digits of a hex, octal or binary literal after its prefix
+    fn radix_number(&mut self, radix: u32, name: &str) -> Token {
+        let digits_start = self.pos;
+        self.digits(radix);
+
+        if is_ident_continue(self.peek()) {
+            self.skip_alphanumeric();
+            return self.error(&format!("Invalid digit in {} literal", name));
+        }
+
+        let lexeme = self.lexeme(digits_start, self.pos);
+        if lexeme.is_empty() {
+            return self.error(&format!("Missing digits in {} literal", name));
+        }
+
+        if misplaced_underscore(&lexeme) {
+            return self.error("Misplaced '_' in number literal");
+        }
+
+        let digits: String = lexeme.chars().filter(|&c| c != '_').collect();
+        self.int_token(&digits, radix)
+    }
+
+    /// integer literals too large for an i64 become bigints
+    fn int_token(&self, digits: &str, radix: u32) -> Token {
+        match i64::from_str_radix(digits, radix) {
+            Ok(num) => self.make_token(TokenType::Int(num)),
+            Err(_) => {
+                let num = BigInt::from_str_radix(digits, radix).unwrap();
+                self.make_token(TokenType::BigInt(num))
+            }
         }
+    }
 
-        let string = &self.source[self.start..self.pos];
-        let num: f64 = string.parse().unwrap();
-        self.make_token(TokenType::Number(num))
+    /// consumes digits of the given radix along with `_` separators
+    fn digits(&mut self, radix: u32) {
+        while self.peek().is_digit(radix) || self.peek() == '_' {
+            self.advance();
+        }
+    }
+
+    /// consumes the rest of a malformed literal so scanning resumes after it
+    fn skip_alphanumeric(&mut self) {
+        while is_ident_continue(self.peek()) {
+            self.advance();
+        }
     }
 
     fn identifier(&mut self) -> Token {
-        while is_alpha(self.peek()) || is_digit(self.peek()) {
+        while is_ident_continue(self.peek()) {
             self.advance();
         }
 
@@ -204,53 +761,121 @@ impl Scanner {
         self.make_token(tok)
     }
 
-    fn skip_whitespace(&mut self) {
+    /// skips whitespace and comments, recording comments as trivia. Returns a
+    /// newline token if a line break ended the statement, or an error token
+    /// for an unterminated block comment.
+    fn skip_whitespace(&mut self) -> Result<Option<Token>, Token> {
+        let mut newline = None;
         loop {
             match self.peek() {
-                ' ' | '\r' | '\t' => {
+                '\n' => {
+                    if newline.is_none() && self.newline_is_significant() {
+                        newline = Some((self.line, self.column()));
+                    }
                     self.advance();
                 }
-                '\n' => {
-                    self.line += 1;
+                c if c.is_whitespace() => {
                     self.advance();
                 }
+                '#' if self.peek_next() == '[' => self.block_comment()?,
                 '#' => {
+                    let line = self.line;
+                    let start = self.pos;
                     while self.peek() != '\n' && !self.eof() {
                         self.advance();
                     }
+
+                    let comment = self.lexeme(start, self.pos);
+                    let trivia = match comment.strip_prefix("##") {
+                        Some(doc) => Trivia {
+                            kind: CommentKind::Doc,
+                            text: String::from(doc.strip_prefix(' ').unwrap_or(doc)),
+                            line,
+                        },
+                        None => Trivia {
+                            kind: CommentKind::Line,
+                            text: String::from(&comment[1..]),
+                            line,
+                        },
+                    };
+                    self.trivia.push(trivia);
                 }
                 _ => break,
             }
         }
+
+        match newline {
+            Some((line, col)) if !self.eof() && !self.continues_statement() => {
+                Ok(Some(Token::new(TokenType::Newline, line, col, 1)))
+            }
+            _ => Ok(None),
+        }
     }
 
-    fn peek_next(&self) -> char {
-        let next = self.pos + 1;
-        if self.eof() {
-            '\0'
-        } else {
-            self.source[next..].chars().next().unwrap()
+    fn block_comment(&mut self) -> Result<(), Token> {
+        self.mark_start();
+
+        // consume the opening '#['
+        self.advance();
+        self.advance();
+
+        let mut depth = 1;
+        while depth > 0 {
+            if self.eof() {
+                return Err(self.error("Unterminated block comment"));
+            }
+
+            if self.peek() == '#' && self.peek_next() == '[' {
+                depth += 1;
+                self.advance();
+            } else if self.peek() == ']' && self.peek_next() == '#' {
+                depth -= 1;
+                self.advance();
+            }
+            self.advance();
         }
+
+        let text = self.lexeme(self.start + 2, self.pos - 2);
+        self.trivia.push(Trivia {
+            kind: CommentKind::Block,
+            text,
+            line: self.start_line,
+        });
+
+        Ok(())
+    }
+
+    fn peek_next(&self) -> char {
+        self.source.get(self.pos + 1).copied().unwrap_or('\0')
     }
 
     fn identifier_type(&mut self) -> TokenType {
-        let c = self.source[self.start..].chars().next().unwrap();
+        let c = self.source[self.start];
         let t = match c {
             'a' => self.check_keyword(1, 2, "nd", TokenType::And),
-            'c' => self.check_keyword(1, 4, "lass", TokenType::Class),
-            'e' => self.check_keyword(1, 3, "lse", TokenType::Else),
-            'i' => self.check_keyword(1, 1, "f", TokenType::If),
+            'c' => self
+                .check_keyword(1, 4, "lass", TokenType::Class)
+                .or_else(|| self.check_keyword(1, 4, "atch", TokenType::Catch)),
+            'e' => self
+                .check_keyword(1, 3, "lse", TokenType::Else)
+                .or_else(|| self.check_keyword(1, 5, "xport", TokenType::Export)),
+            'i' => self
+                .check_keyword(1, 1, "f", TokenType::If)
+                .or_else(|| self.check_keyword(1, 5, "mport", TokenType::Import)),
             'f' => {
                 if self.pos - self.start > 1 {
-                    match self.source.chars().nth(self.start + 1).unwrap() {
+                    match self.source[self.start + 1] {
                         'a' => self.check_keyword(2, 3, "lse", TokenType::False),
+                        'i' => self.check_keyword(2, 5, "nally", TokenType::Finally),
                         'o' => self.check_keyword(2, 1, "r", TokenType::For),
+                        'r' => self.check_keyword(2, 2, "om", TokenType::From),
                         _ => None,
                     }
                 } else {
                     None
                 }
             }
+            'm' => self.check_keyword(1, 4, "atch", TokenType::Match),
             'n' => self.check_keyword(1, 2, "il", TokenType::Nil),
             'o' => self.check_keyword(1, 1, "r", TokenType::Or),
             'p' => self.check_keyword(1, 4, "rint", TokenType::Print),
@@ -258,9 +883,13 @@ impl Scanner {
             's' => self.check_keyword(1, 4, "uper", TokenType::Super),
             't' => {
                 if self.pos - self.start > 1 {
-                    match self.source.chars().nth(self.start + 1).unwrap() {
-                        'h' => self.check_keyword(2, 2, "is", TokenType::This),
-                        'r' => self.check_keyword(2, 2, "ue", TokenType::True),
+                    match self.source[self.start + 1] {
+                        'h' => self
+                            .check_keyword(2, 2, "is", TokenType::This)
+                            .or_else(|| self.check_keyword(2, 3, "row", TokenType::Throw)),
+                        'r' => self
+                            .check_keyword(2, 2, "ue", TokenType::True)
+                            .or_else(|| self.check_keyword(2, 1, "y", TokenType::Try)),
                         _ => None,
                     }
                 } else {
@@ -275,11 +904,11 @@ impl Scanner {
         if let Some(token_type) = t {
             token_type
         } else {
-            let ident = &self.source[self.start..self.pos];
+            let ident = self.lexeme(self.start, self.pos);
             if ident == "fn" {
                 TokenType::Fn
             } else {
-                TokenType::Identifier(String::from(ident))
+                TokenType::Identifier(ident)
             }
         }
     }
@@ -292,7 +921,10 @@ impl Scanner {
         token_type: TokenType,
     ) -> Option<TokenType> {
         if (self.pos - self.start == start + len)
-            && &self.source[self.start + start..self.start + start + len] == rest
+            && self.source[self.start + start..self.start + start + len]
+                .iter()
+                .copied()
+                .eq(rest.chars())
         {
             Some(token_type)
         } else {
@@ -376,6 +1008,45 @@ mod tests {
         );
     }
 
+    #[test]
+    fn finds_integer() {
+        let mut scanner = Scanner::new(String::from("1234"));
+        let tokens = scanner.scan_all();
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0], Token::new(TokenType::Int(1234), 1, 0, 4));
+    }
+
+    #[test]
+    fn promotes_large_integer_to_bigint() {
+        let mut scanner = Scanner::new(String::from("99999999999999999999"));
+        let tokens = scanner.scan_all();
+        let num: BigInt = "99999999999999999999".parse().unwrap();
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0], Token::new(TokenType::BigInt(num), 1, 0, 20));
+    }
+
+    #[test]
+    fn scans_integer_operators() {
+        let mut scanner = Scanner::new(String::from("// << >> ^ /"));
+        let tokens = scanner.scan_all();
+        assert_eq!(tokens.len(), 5);
+        assert_eq!(tokens[0], Token::new(TokenType::SlashSlash, 1, 0, 2));
+        assert_eq!(tokens[1], Token::new(TokenType::ShiftLeft, 1, 3, 2));
+        assert_eq!(tokens[2], Token::new(TokenType::ShiftRight, 1, 6, 2));
+        assert_eq!(tokens[3], Token::new(TokenType::BitwiseXor, 1, 9, 1));
+        assert_eq!(tokens[4], Token::new(TokenType::Slash, 1, 11, 1));
+    }
+
+    #[test]
+    fn finds_star_operators() {
+        let mut scanner = Scanner::new(String::from("** * *="));
+        let tokens = scanner.scan_all();
+        assert_eq!(tokens.len(), 3);
+        assert_eq!(tokens[0], Token::new(TokenType::StarStar, 1, 0, 2));
+        assert_eq!(tokens[1], Token::new(TokenType::Star, 1, 3, 1));
+        assert_eq!(tokens[2], Token::new(TokenType::StarEqual, 1, 5, 2));
+    }
+
     #[test]
     fn finds_number() {
         let mut scanner = Scanner::new(String::from("12.34"));
@@ -383,4 +1054,587 @@ mod tests {
         assert_eq!(tokens.len(), 1);
         assert_eq!(tokens[0], Token::new(TokenType::Number(12.34), 1, 0, 5));
     }
+
+    #[test]
+    fn finds_radix_integers() {
+        let mut scanner = Scanner::new(String::from("0xff 0o17 0b1010 0XFF"));
+        let tokens = scanner.scan_all();
+        assert_eq!(tokens.len(), 4);
+        assert_eq!(tokens[0], Token::new(TokenType::Int(255), 1, 0, 4));
+        assert_eq!(tokens[1], Token::new(TokenType::Int(15), 1, 5, 4));
+        assert_eq!(tokens[2], Token::new(TokenType::Int(10), 1, 10, 6));
+        assert_eq!(tokens[3], Token::new(TokenType::Int(255), 1, 17, 4));
+    }
+
+    #[test]
+    fn ignores_underscore_separators() {
+        let mut scanner = Scanner::new(String::from("1_000_000 0xffff_ffff 1_0.5"));
+        let tokens = scanner.scan_all();
+        assert_eq!(tokens.len(), 3);
+        assert_eq!(tokens[0].token_type, TokenType::Int(1_000_000));
+        assert_eq!(tokens[1].token_type, TokenType::Int(0xffff_ffff));
+        assert_eq!(tokens[2].token_type, TokenType::Number(10.5));
+    }
+
+    #[test]
+    fn finds_scientific_notation() {
+        let mut scanner = Scanner::new(String::from("1.5e-3 2E10 3e+2"));
+        let tokens = scanner.scan_all();
+        assert_eq!(tokens.len(), 3);
+        assert_eq!(tokens[0], Token::new(TokenType::Number(1.5e-3), 1, 0, 6));
+        assert_eq!(tokens[1].token_type, TokenType::Number(2e10));
+        assert_eq!(tokens[2].token_type, TokenType::Number(300.0));
+    }
+
+    #[test]
+    fn promotes_large_hex_literal_to_bigint() {
+        let mut scanner = Scanner::new(String::from("0xffff_ffff_ffff_ffff"));
+        let tokens = scanner.scan_all();
+        let num: BigInt = "18446744073709551615".parse().unwrap();
+        assert_eq!(tokens[0].token_type, TokenType::BigInt(num));
+    }
+
+    #[test]
+    fn reports_malformed_number_literals() {
+        let sources = [
+            "0x", "0b102", "0o8", "1e", "1.5e+", "12abc", "1__0", "1_", "0x_f",
+        ];
+        for source in sources {
+            let mut scanner = Scanner::new(String::from(source));
+            let tokens = scanner.scan_all();
+            assert_eq!(tokens.len(), 1, "{}", source);
+            assert!(
+                matches!(tokens[0].token_type, TokenType::Error(_)),
+                "{}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn resumes_after_malformed_number_literal() {
+        let mut scanner = Scanner::new(String::from("0b12 nil"));
+        let tokens = scanner.scan_all();
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens[1], Token::new(TokenType::Nil, 1, 5, 3));
+    }
+    #[test]
+    fn finds_empty_string() {
+        let mut scanner = Scanner::new(String::from("'' \"\""));
+        let tokens = scanner.scan_all();
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(
+            tokens[0],
+            Token::new(TokenType::String(String::new()), 1, 0, 2)
+        );
+        assert_eq!(
+            tokens[1],
+            Token::new(TokenType::String(String::new()), 1, 3, 2)
+        );
+    }
+
+    #[test]
+    fn processes_escape_sequences() {
+        let mut scanner = Scanner::new(String::from(r#"'a\n\t\\\'\"\0\u{e9}\u{1F600}'"#));
+        let tokens = scanner.scan_all();
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(
+            tokens[0].token_type,
+            TokenType::String(String::from("a\n\t\\'\"\0\u{e9}\u{1F600}"))
+        );
+    }
+
+    #[test]
+    fn reports_invalid_escape_at_its_column() {
+        let mut scanner = Scanner::new(String::from("x\n  'ab\\qc' nil"));
+        let tokens = scanner.scan_all();
+        assert_eq!(tokens.len(), 4);
+        assert_eq!(
+            tokens[2],
+            Token::new(
+                TokenType::Error(String::from("Invalid escape sequence '\\q'")),
+                2,
+                5,
+                2
+            )
+        );
+        assert_eq!(tokens[3], Token::new(TokenType::Nil, 2, 10, 3));
+    }
+
+    #[test]
+    fn reports_invalid_unicode_escape() {
+        let sources = [r"'\u{110000}'", r"'\u{}'", r"'\u41'", r"'\u{1234567}'"];
+        for source in sources {
+            let mut scanner = Scanner::new(String::from(source));
+            let tokens = scanner.scan_all();
+            assert_eq!(tokens.len(), 1, "{}", source);
+            assert_eq!(
+                tokens[0].token_type,
+                TokenType::Error(String::from("Invalid unicode escape")),
+                "{}",
+                source
+            );
+            assert_eq!(tokens[0].col, 1);
+        }
+    }
+
+    #[test]
+    fn raw_strings_skip_escapes() {
+        let mut scanner = Scanner::new(String::from(r#"r'C:\new\dir' r"\u{41}" rest"#));
+        let tokens = scanner.scan_all();
+        assert_eq!(tokens.len(), 3);
+        assert_eq!(
+            tokens[0],
+            Token::new(TokenType::String(String::from(r"C:\new\dir")), 1, 0, 13)
+        );
+        assert_eq!(
+            tokens[1].token_type,
+            TokenType::String(String::from(r"\u{41}"))
+        );
+        assert_eq!(
+            tokens[2].token_type,
+            TokenType::Identifier(String::from("rest"))
+        );
+    }
+
+    #[test]
+    fn strips_indentation_from_multiline_strings() {
+        let source = "x \"\"\"\n    first\n      second\\tline\n\n    third\n    \"\"\" nil";
+        let mut scanner = Scanner::new(String::from(source));
+        let tokens = scanner.scan_all();
+        assert_eq!(tokens.len(), 3);
+        assert_eq!(
+            tokens[1].token_type,
+            TokenType::String(String::from("first\n  second\tline\n\nthird"))
+        );
+        assert_eq!(tokens[1].line, 1);
+        assert_eq!(tokens[2], Token::new(TokenType::Nil, 6, 8, 3));
+    }
+
+    #[test]
+    fn multiline_string_on_one_line() {
+        let mut scanner = Scanner::new(String::from("'''it's \"quoted\"'''"));
+        let tokens = scanner.scan_all();
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(
+            tokens[0].token_type,
+            TokenType::String(String::from("it's \"quoted\""))
+        );
+    }
+
+    #[test]
+    fn reports_unterminated_strings() {
+        for source in ["'abc", "\"\"\"\n  abc\n"] {
+            let mut scanner = Scanner::new(String::from(source));
+            let tokens = scanner.scan_all();
+            assert_eq!(
+                tokens[0].token_type,
+                TokenType::Error(String::from("Unterminated string"))
+            );
+        }
+    }
+    #[test]
+    fn splits_interpolated_strings() {
+        let mut scanner = Scanner::new(String::from("'a {b} c {d}'"));
+        let tokens = scanner.scan_all();
+        let types: Vec<TokenType> = tokens.into_iter().map(|t| t.token_type).collect();
+        assert_eq!(
+            types,
+            vec![
+                TokenType::Interpolation(String::from("a ")),
+                TokenType::Identifier(String::from("b")),
+                TokenType::Interpolation(String::from(" c ")),
+                TokenType::Identifier(String::from("d")),
+                TokenType::String(String::new()),
+            ]
+        );
+    }
+
+    #[test]
+    fn scans_nested_interpolation_and_braces() {
+        let mut scanner = Scanner::new(String::from("\"x{f(\"y{z}\") + {}}\\{w\\}\""));
+        let tokens = scanner.scan_all();
+        let types: Vec<TokenType> = tokens.into_iter().map(|t| t.token_type).collect();
+        assert_eq!(
+            types,
+            vec![
+                TokenType::Interpolation(String::from("x")),
+                TokenType::Identifier(String::from("f")),
+                TokenType::LeftParen,
+                TokenType::Interpolation(String::from("y")),
+                TokenType::Identifier(String::from("z")),
+                TokenType::String(String::new()),
+                TokenType::RightParen,
+                TokenType::Plus,
+                TokenType::LeftBrace,
+                TokenType::RightBrace,
+                TokenType::String(String::from("{w}")),
+            ]
+        );
+    }
+
+    #[test]
+    fn interpolates_multiline_strings() {
+        let source = "'''\n  a {b + '}'}\n  c\n  ''' nil";
+        let mut scanner = Scanner::new(String::from(source));
+        let tokens = scanner.scan_all();
+        let types: Vec<TokenType> = tokens.into_iter().map(|t| t.token_type).collect();
+        assert_eq!(
+            types,
+            vec![
+                TokenType::Interpolation(String::from("a ")),
+                TokenType::Identifier(String::from("b")),
+                TokenType::Plus,
+                TokenType::String(String::from("}")),
+                TokenType::String(String::from("\nc")),
+                TokenType::Nil,
+            ]
+        );
+    }
+
+    #[test]
+    fn raw_strings_do_not_interpolate() {
+        let mut scanner = Scanner::new(String::from("r'{a}'"));
+        let tokens = scanner.scan_all();
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].token_type, TokenType::String(String::from("{a}")));
+    }
+    #[test]
+    fn counts_columns_in_characters() {
+        let mut scanner = Scanner::new(String::from("'😀é' nil # 注释 🎉\n  '中文' true"));
+        let tokens = scanner.scan_all();
+        assert_eq!(tokens.len(), 5);
+        assert_eq!(
+            tokens[0],
+            Token::new(TokenType::String(String::from("😀é")), 1, 0, 4)
+        );
+        assert_eq!(tokens[1], Token::new(TokenType::Nil, 1, 5, 3));
+        assert_eq!(tokens[2], Token::new(TokenType::Newline, 1, 15, 1));
+        assert_eq!(
+            tokens[3],
+            Token::new(TokenType::String(String::from("中文")), 2, 2, 4)
+        );
+        assert_eq!(tokens[4], Token::new(TokenType::True, 2, 7, 4));
+    }
+
+    #[test]
+    fn scans_unicode_identifiers() {
+        let mut scanner = Scanner::new(String::from("变量 = café + Ω_1 + e\u{301}"));
+        let tokens = scanner.scan_all();
+        assert_eq!(tokens.len(), 7);
+        assert_eq!(
+            tokens[0],
+            Token::new(TokenType::Identifier(String::from("变量")), 1, 0, 2)
+        );
+        assert_eq!(
+            tokens[2],
+            Token::new(TokenType::Identifier(String::from("café")), 1, 5, 4)
+        );
+        assert_eq!(
+            tokens[4],
+            Token::new(TokenType::Identifier(String::from("Ω_1")), 1, 12, 3)
+        );
+        assert_eq!(
+            tokens[6],
+            Token::new(TokenType::Identifier(String::from("e\u{301}")), 1, 18, 2)
+        );
+    }
+
+    #[test]
+    fn emoji_are_not_identifiers() {
+        let mut scanner = Scanner::new(String::from("a🎉"));
+        let tokens = scanner.scan_all();
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(
+            tokens[1],
+            Token::new(
+                TokenType::Error(String::from("Unexpected character '🎉'")),
+                1,
+                1,
+                1
+            )
+        );
+    }
+
+    #[test]
+    fn reports_error_column_after_multibyte_characters() {
+        let mut scanner = Scanner::new(String::from("'日本語 \\q'"));
+        let tokens = scanner.scan_all();
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].col, 5);
+    }
+
+    #[test]
+    fn strips_indentation_around_multibyte_characters() {
+        let source = "'''\n    ✨ sparkle\n    火\n    '''";
+        let mut scanner = Scanner::new(String::from(source));
+        let tokens = scanner.scan_all();
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(
+            tokens[0].token_type,
+            TokenType::String(String::from("✨ sparkle\n火"))
+        );
+    }
+
+    #[test]
+    fn reports_unexpected_characters_and_keeps_scanning() {
+        let mut scanner = Scanner::new(String::from("a @ b\n$"));
+        let tokens = scanner.scan_all();
+        assert_eq!(tokens.len(), 5);
+        assert_eq!(
+            tokens[1],
+            Token::new(
+                TokenType::Error(String::from("Unexpected character '@'")),
+                1,
+                2,
+                1
+            )
+        );
+        assert_eq!(
+            tokens[2],
+            Token::new(TokenType::Identifier(String::from("b")), 1, 4, 1)
+        );
+        assert_eq!(tokens[3].token_type, TokenType::Newline);
+        assert_eq!(tokens[4].line, 2);
+        assert_eq!(tokens[4].col, 0);
+    }
+
+    #[test]
+    fn skips_nested_block_comments() {
+        let mut scanner = Scanner::new(String::from("a #[ one\n #[ two ]# ]# b"));
+        let tokens = scanner.scan_all();
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(
+            tokens[1],
+            Token::new(TokenType::Identifier(String::from("b")), 2, 14, 1)
+        );
+        assert_eq!(
+            scanner.trivia(),
+            &[Trivia {
+                kind: CommentKind::Block,
+                text: String::from(" one\n #[ two ]# "),
+                line: 1,
+            }]
+        );
+    }
+
+    #[test]
+    fn reports_unterminated_block_comment() {
+        let mut scanner = Scanner::new(String::from("nil #[ never closed"));
+        let tokens = scanner.scan_all();
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(
+            tokens[1],
+            Token::new(
+                TokenType::Error(String::from("Unterminated block comment")),
+                1,
+                4,
+                15
+            )
+        );
+    }
+
+    #[test]
+    fn retains_comments_as_trivia() {
+        let source = "# plain\n## doc one\n##doc two\nfn f";
+        let mut scanner = Scanner::new(String::from(source));
+        scanner.scan_all();
+        let kinds: Vec<CommentKind> = scanner.trivia().iter().map(|t| t.kind.clone()).collect();
+        assert_eq!(
+            kinds,
+            vec![CommentKind::Line, CommentKind::Doc, CommentKind::Doc]
+        );
+        assert_eq!(
+            scanner.doc_comment(4),
+            Some(String::from("doc one\ndoc two"))
+        );
+        assert_eq!(scanner.doc_comment(3), Some(String::from("doc one")));
+        assert_eq!(scanner.doc_comment(2), None);
+    }
+
+    fn token_types(source: &str) -> Vec<TokenType> {
+        let mut scanner = Scanner::new(String::from(source));
+        scanner
+            .scan_all()
+            .into_iter()
+            .map(|t| t.token_type)
+            .collect()
+    }
+
+    #[test]
+    fn emits_newline_after_statement_ending_tokens() {
+        assert_eq!(
+            token_types("return\nx = 1\n\n\nf()\n"),
+            vec![
+                TokenType::Return,
+                TokenType::Newline,
+                TokenType::Identifier(String::from("x")),
+                TokenType::Equal,
+                TokenType::Int(1),
+                TokenType::Newline,
+                TokenType::Identifier(String::from("f")),
+                TokenType::LeftParen,
+                TokenType::RightParen,
+            ]
+        );
+    }
+
+    #[test]
+    fn continues_after_trailing_operator_and_inside_parens() {
+        assert_eq!(
+            token_types("a +\nb\n(c\n, d)"),
+            vec![
+                TokenType::Identifier(String::from("a")),
+                TokenType::Plus,
+                TokenType::Identifier(String::from("b")),
+                TokenType::Newline,
+                TokenType::LeftParen,
+                TokenType::Identifier(String::from("c")),
+                TokenType::Comma,
+                TokenType::Identifier(String::from("d")),
+                TokenType::RightParen,
+            ]
+        );
+    }
+
+    #[test]
+    fn newlines_are_significant_inside_braces_within_parens() {
+        assert_eq!(
+            token_types("f({\na\nb\n})"),
+            vec![
+                TokenType::Identifier(String::from("f")),
+                TokenType::LeftParen,
+                TokenType::LeftBrace,
+                TokenType::Identifier(String::from("a")),
+                TokenType::Newline,
+                TokenType::Identifier(String::from("b")),
+                TokenType::Newline,
+                TokenType::RightBrace,
+                TokenType::RightParen,
+            ]
+        );
+    }
+
+    #[test]
+    fn continues_before_leading_dot_and_else() {
+        assert_eq!(
+            token_types("a\n  .b\n}\n# comment\nelse {}\nelsewhere"),
+            vec![
+                TokenType::Identifier(String::from("a")),
+                TokenType::Dot,
+                TokenType::Identifier(String::from("b")),
+                TokenType::Newline,
+                TokenType::RightBrace,
+                TokenType::Else,
+                TokenType::LeftBrace,
+                TokenType::RightBrace,
+                TokenType::Newline,
+                TokenType::Identifier(String::from("elsewhere")),
+            ]
+        );
+    }
+
+    #[test]
+    fn catch_and_finally_continue_a_statement() {
+        assert_eq!(
+            token_types("try {\n  throw e\n}\ncatch e {}\nfinally {}\ncatcher"),
+            vec![
+                TokenType::Try,
+                TokenType::LeftBrace,
+                TokenType::Throw,
+                TokenType::Identifier(String::from("e")),
+                TokenType::Newline,
+                TokenType::RightBrace,
+                TokenType::Catch,
+                TokenType::Identifier(String::from("e")),
+                TokenType::LeftBrace,
+                TokenType::RightBrace,
+                TokenType::Finally,
+                TokenType::LeftBrace,
+                TokenType::RightBrace,
+                TokenType::Newline,
+                TokenType::Identifier(String::from("catcher")),
+            ]
+        );
+    }
+
+    #[test]
+    fn question_mark_ends_a_statement() {
+        assert_eq!(
+            token_types("x = f()?\ny"),
+            vec![
+                TokenType::Identifier(String::from("x")),
+                TokenType::Equal,
+                TokenType::Identifier(String::from("f")),
+                TokenType::LeftParen,
+                TokenType::RightParen,
+                TokenType::Question,
+                TokenType::Newline,
+                TokenType::Identifier(String::from("y")),
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_compound_assignment_tokens() {
+        assert_eq!(
+            token_types("+= -= *= /= %= &= |= // & |"),
+            vec![
+                TokenType::PlusEqual,
+                TokenType::MinusEqual,
+                TokenType::StarEqual,
+                TokenType::SlashEqual,
+                TokenType::ModEqual,
+                TokenType::AmpEqual,
+                TokenType::PipeEqual,
+                TokenType::SlashSlash,
+                TokenType::BitwiseAnd,
+                TokenType::BitwiseOr,
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_match_tokens() {
+        assert_eq!(
+            token_types("match [x] { 1..=2 => a, 3..4 => b.c }"),
+            vec![
+                TokenType::Match,
+                TokenType::LeftBracket,
+                TokenType::Identifier(String::from("x")),
+                TokenType::RightBracket,
+                TokenType::LeftBrace,
+                TokenType::Int(1),
+                TokenType::DotDotEqual,
+                TokenType::Int(2),
+                TokenType::FatArrow,
+                TokenType::Identifier(String::from("a")),
+                TokenType::Comma,
+                TokenType::Int(3),
+                TokenType::DotDot,
+                TokenType::Int(4),
+                TokenType::FatArrow,
+                TokenType::Identifier(String::from("b")),
+                TokenType::Dot,
+                TokenType::Identifier(String::from("c")),
+                TokenType::RightBrace,
+            ]
+        );
+    }
+
+    #[test]
+    fn newlines_are_not_significant_inside_brackets() {
+        assert_eq!(
+            token_types("[1,\n2]\nx"),
+            vec![
+                TokenType::LeftBracket,
+                TokenType::Int(1),
+                TokenType::Comma,
+                TokenType::Int(2),
+                TokenType::RightBracket,
+                TokenType::Newline,
+                TokenType::Identifier(String::from("x")),
+            ]
+        );
+    }
 }
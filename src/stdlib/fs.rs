@@ -0,0 +1,273 @@
+use super::io_error;
+use crate::native::{arity, string_arg};
+use crate::stdlib::io::trim_newline;
+use crate::value::Value;
+use crate::vm::Vm;
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::UNIX_EPOCH;
+
+/// a file opened with `fs.open`. Reads go through a buffer so a file can be
+/// read a line at a time
+#[derive(Debug)]
+pub struct FileHandle {
+    pub path: String,
+    state: FileState,
+}
+
+#[derive(Debug)]
+enum FileState {
+    Reading(BufReader<File>),
+    Writing(File),
+    Closed,
+}
+
+pub fn module() -> Value {
+    super::module(&[
+        ("open", open),
+        ("read", read),
+        ("write", write),
+        ("append", append),
+        ("list", list),
+        ("stat", stat),
+        ("exists", exists),
+        ("mkdir", mkdir),
+        ("remove", remove),
+        ("rename", rename),
+        ("join", join),
+        ("dirname", dirname),
+        ("basename", basename),
+        ("extension", extension),
+    ])
+}
+
+/// opens a file to read with mode 'r', or to write with 'w' or 'a' for
+/// appending
+fn open(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    arity("open", args, 2)?;
+    let path = string_arg("open", args, 0)?;
+
+    let state = match string_arg("open", args, 1)? {
+        "r" => File::open(path).map(|file| FileState::Reading(BufReader::new(file))),
+        "w" => File::create(path).map(FileState::Writing),
+        "a" => OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(path)
+            .map(FileState::Writing),
+        mode => {
+            return Err(format!(
+                "Unknown file mode '{}', expected 'r', 'w' or 'a'",
+                mode
+            ))
+        }
+    };
+
+    match state {
+        Ok(state) => Ok(Value::File(Rc::new(RefCell::new(FileHandle {
+            path: String::from(path),
+            state,
+        })))),
+        Err(e) => Err(io_error("open", path, e)),
+    }
+}
+
+/// methods of an open file
+pub fn file_method(
+    file: &RefCell<FileHandle>,
+    name: &str,
+    args: &[Value],
+) -> Result<Value, String> {
+    let mut file = file.borrow_mut();
+    let path = file.path.clone();
+
+    match (name, &mut file.state) {
+        ("read", FileState::Reading(reader)) => {
+            arity(name, args, 0)?;
+            let mut contents = String::new();
+            match reader.read_to_string(&mut contents) {
+                Ok(_) => Ok(Value::String(contents)),
+                Err(e) => Err(io_error("read", &path, e)),
+            }
+        }
+        // the next line without its line break, nil at the end of the file
+        ("read_line", FileState::Reading(reader)) => {
+            arity(name, args, 0)?;
+            let mut line = String::new();
+            match reader.read_line(&mut line) {
+                Ok(0) => Ok(Value::Nil),
+                Ok(_) => Ok(Value::String(String::from(trim_newline(&line)))),
+                Err(e) => Err(io_error("read", &path, e)),
+            }
+        }
+        ("write", FileState::Writing(writer)) => {
+            arity(name, args, 1)?;
+            match write!(writer, "{}", args[0]) {
+                Ok(_) => Ok(Value::Nil),
+                Err(e) => Err(io_error("write", &path, e)),
+            }
+        }
+        ("close", _) => {
+            arity(name, args, 0)?;
+            file.state = FileState::Closed;
+            Ok(Value::Nil)
+        }
+        ("read" | "read_line", _) => Err(format!("File '{}' is not open for reading", path)),
+        ("write", _) => Err(format!("File '{}' is not open for writing", path)),
+        _ => Err(format!("Undefined method '{}' on file", name)),
+    }
+}
+
+/// the whole contents of a file
+fn read(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    arity("read", args, 1)?;
+    let path = string_arg("read", args, 0)?;
+    match fs::read_to_string(path) {
+        Ok(contents) => Ok(Value::String(contents)),
+        Err(e) => Err(io_error("read", path, e)),
+    }
+}
+
+/// replaces a file's contents, creating it if needed
+fn write(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    arity("write", args, 2)?;
+    let path = string_arg("write", args, 0)?;
+    match fs::write(path, args[1].to_string()) {
+        Ok(_) => Ok(Value::Nil),
+        Err(e) => Err(io_error("write", path, e)),
+    }
+}
+
+fn append(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    arity("append", args, 2)?;
+    let path = string_arg("append", args, 0)?;
+    let result = OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(path)
+        .and_then(|mut file| write!(file, "{}", args[1]));
+    match result {
+        Ok(_) => Ok(Value::Nil),
+        Err(e) => Err(io_error("append to", path, e)),
+    }
+}
+
+/// names of the entries in a directory, sorted
+fn list(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    arity("list", args, 1)?;
+    let path = string_arg("list", args, 0)?;
+
+    let entries = fs::read_dir(path).map_err(|e| io_error("list", path, e))?;
+    let mut names = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(|e| io_error("list", path, e))?;
+        names.push(entry.file_name().to_string_lossy().into_owned());
+    }
+    names.sort();
+
+    Ok(Value::from(
+        names.into_iter().map(Value::String).collect::<Vec<_>>(),
+    ))
+}
+
+/// a map of a file's size, type and modification time in seconds since 1970
+fn stat(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    arity("stat", args, 1)?;
+    let path = string_arg("stat", args, 0)?;
+    let metadata = fs::metadata(path).map_err(|e| io_error("stat", path, e))?;
+
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(Value::Nil, |time| Value::Number(time.as_secs_f64()));
+
+    let mut stat = BTreeMap::new();
+    stat.insert(String::from("size"), Value::Int(metadata.len() as i64));
+    stat.insert(String::from("is_file"), Value::Bool(metadata.is_file()));
+    stat.insert(String::from("is_dir"), Value::Bool(metadata.is_dir()));
+    stat.insert(String::from("modified"), modified);
+    Ok(Value::from(stat))
+}
+
+fn exists(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    arity("exists", args, 1)?;
+    Ok(Value::Bool(
+        Path::new(string_arg("exists", args, 0)?).exists(),
+    ))
+}
+
+/// creates a directory and any missing parents
+fn mkdir(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    arity("mkdir", args, 1)?;
+    let path = string_arg("mkdir", args, 0)?;
+    match fs::create_dir_all(path) {
+        Ok(_) => Ok(Value::Nil),
+        Err(e) => Err(io_error("create", path, e)),
+    }
+}
+
+/// removes a file or an empty directory
+fn remove(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    arity("remove", args, 1)?;
+    let path = string_arg("remove", args, 0)?;
+    let result = if Path::new(path).is_dir() {
+        fs::remove_dir(path)
+    } else {
+        fs::remove_file(path)
+    };
+    match result {
+        Ok(_) => Ok(Value::Nil),
+        Err(e) => Err(io_error("remove", path, e)),
+    }
+}
+
+fn rename(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    arity("rename", args, 2)?;
+    let from = string_arg("rename", args, 0)?;
+    let to = string_arg("rename", args, 1)?;
+    match fs::rename(from, to) {
+        Ok(_) => Ok(Value::Nil),
+        Err(e) => Err(io_error("rename", from, e)),
+    }
+}
+
+/// joins any number of path parts with the separator
+fn join(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    let mut path = PathBuf::new();
+    for i in 0..args.len() {
+        path.push(string_arg("join", args, i)?);
+    }
+    Ok(Value::String(path.to_string_lossy().into_owned()))
+}
+
+/// the directory part of a path, '' when there is none
+fn dirname(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    arity("dirname", args, 1)?;
+    let path = Path::new(string_arg("dirname", args, 0)?);
+    let dir = path.parent().map(|dir| dir.to_string_lossy().into_owned());
+    Ok(Value::String(dir.unwrap_or_default()))
+}
+
+fn basename(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    arity("basename", args, 1)?;
+    let path = Path::new(string_arg("basename", args, 0)?);
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned());
+    Ok(Value::String(name.unwrap_or_default()))
+}
+
+/// the extension without its dot, nil when there is none
+fn extension(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    arity("extension", args, 1)?;
+    let path = Path::new(string_arg("extension", args, 0)?);
+    Ok(path.extension().map_or(Value::Nil, |ext| {
+        Value::String(ext.to_string_lossy().into_owned())
+    }))
+}
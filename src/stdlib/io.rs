@@ -0,0 +1,44 @@
+use crate::native::arity;
+use crate::value::Value;
+use crate::vm::Vm;
+
+use std::io::{stdin, stdout, BufRead, Write};
+
+pub fn module() -> Value {
+    super::module(&[("read_line", read_line), ("write", write), ("error", error)])
+}
+
+/// reads a line from stdin without its line break, nil at the end of input
+fn read_line(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    arity("read_line", args, 0)?;
+
+    let mut line = String::new();
+    match stdin().lock().read_line(&mut line) {
+        Ok(0) => Ok(Value::Nil),
+        Ok(_) => Ok(Value::String(String::from(trim_newline(&line)))),
+        Err(e) => Err(format!("Cannot read stdin: {}", e)),
+    }
+}
+
+/// prints to stdout without adding a line break
+fn write(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    arity("write", args, 1)?;
+
+    let mut out = stdout();
+    write!(out, "{}", args[0])
+        .and_then(|_| out.flush())
+        .map_err(|e| format!("Cannot write stdout: {}", e))?;
+    Ok(Value::Nil)
+}
+
+/// prints a line to stderr
+fn error(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    arity("error", args, 1)?;
+    eprintln!("{}", args[0]);
+    Ok(Value::Nil)
+}
+
+pub fn trim_newline(line: &str) -> &str {
+    let line = line.strip_suffix('\n').unwrap_or(line);
+    line.strip_suffix('\r').unwrap_or(line)
+}
@@ -0,0 +1,482 @@
+use crate::bigint::BigInt;
+use crate::native::{arity, int_arg, string_arg};
+use crate::value::Value;
+use crate::vm::Vm;
+
+use std::collections::BTreeMap;
+
+/// deepest nesting of lists and maps `parse` accepts
+const MAX_DEPTH: usize = 512;
+
+pub fn module() -> Value {
+    super::module(&[("parse", parse), ("stringify", stringify)])
+}
+
+/// reads a json document into maps, lists, numbers, strings, bools and nil
+fn parse(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    arity("parse", args, 1)?;
+    let mut reader = Reader::new(string_arg("parse", args, 0)?);
+
+    let value = reader.value(0)?;
+    reader.skip_whitespace();
+    if reader.peek().is_some() {
+        return Err(reader.error("Expect end of input after value"));
+    }
+    Ok(value)
+}
+
+/// writes a value as json, on one line or pretty printed with the given
+/// number of spaces of indentation
+fn stringify(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    let indent = match args.len() {
+        1 => None,
+        2 if matches!(args[1], Value::Nil) => None,
+        2 => match int_arg("stringify", args, 1)? {
+            n @ 0..=16 => Some(n as usize),
+            n => return Err(format!("Indent must be between 0 and 16 but got {}", n)),
+        },
+        _ => {
+            return Err(format!(
+                "stringify() expects 1 or 2 argument(s) but got {}",
+                args.len()
+            ))
+        }
+    };
+
+    let mut writer = Writer {
+        out: String::new(),
+        indent,
+        path: Vec::new(),
+    };
+    writer.value(&args[0], 0)?;
+    Ok(Value::String(writer.out))
+}
+
+/// a cursor over json text that knows its line and column for errors
+struct Reader {
+    chars: Vec<char>,
+    current: usize,
+    line: usize,
+    column: usize,
+}
+
+impl Reader {
+    fn new(text: &str) -> Reader {
+        Reader {
+            chars: text.chars().collect(),
+            current: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+
+    fn error(&self, msg: &str) -> String {
+        format!(
+            "Invalid JSON at line {}, column {}: {}",
+            self.line, self.column, msg
+        )
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.current).copied()
+    }
+
+    fn advance(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.current += 1;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t' | '\n' | '\r')) {
+            self.advance();
+        }
+    }
+
+    fn consume(&mut self, expected: char, msg: &str) -> Result<(), String> {
+        self.skip_whitespace();
+        if self.peek() == Some(expected) {
+            self.advance();
+            Ok(())
+        } else {
+            Err(self.error(msg))
+        }
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Value, String> {
+        self.skip_whitespace();
+        if depth > MAX_DEPTH {
+            return Err(self.error("Too deeply nested"));
+        }
+
+        match self.peek() {
+            Some('{') => self.object(depth),
+            Some('[') => self.array(depth),
+            Some('"') => Ok(Value::String(self.string()?)),
+            Some('-' | '0'..='9') => self.number(),
+            Some(c) if c.is_ascii_alphabetic() => self.literal(),
+            Some(c) => Err(self.error(&format!("Unexpected character '{}'", c))),
+            None => Err(self.error("Expect value but got end of input")),
+        }
+    }
+
+    fn object(&mut self, depth: usize) -> Result<Value, String> {
+        self.advance();
+        let mut entries = BTreeMap::new();
+
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.advance();
+            return Ok(Value::from(entries));
+        }
+
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some('"') {
+                return Err(self.error("Expect string key"));
+            }
+            let key = self.string()?;
+            self.consume(':', "Expect ':' after key")?;
+            entries.insert(key, self.value(depth + 1)?);
+
+            self.skip_whitespace();
+            match self.advance() {
+                Some(',') => continue,
+                Some('}') => return Ok(Value::from(entries)),
+                _ => return Err(self.error("Expect ',' or '}' after value")),
+            }
+        }
+    }
+
+    fn array(&mut self, depth: usize) -> Result<Value, String> {
+        self.advance();
+        let mut items = Vec::new();
+
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.advance();
+            return Ok(Value::from(items));
+        }
+
+        loop {
+            items.push(self.value(depth + 1)?);
+
+            self.skip_whitespace();
+            match self.advance() {
+                Some(',') => continue,
+                Some(']') => return Ok(Value::from(items)),
+                _ => return Err(self.error("Expect ',' or ']' after value")),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.advance();
+        let mut s = String::new();
+
+        loop {
+            match self.advance() {
+                Some('"') => return Ok(s),
+                Some('\\') => {
+                    let c = match self.advance() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => self.unicode_escape()?,
+                        _ => return Err(self.error("Invalid escape sequence")),
+                    };
+                    s.push(c);
+                }
+                Some(c) if c < ' ' => {
+                    return Err(self.error("Unescaped control character in string"));
+                }
+                Some(c) => s.push(c),
+                None => return Err(self.error("Unterminated string")),
+            }
+        }
+    }
+
+    /// `\uXXXX`, where characters outside the basic plane are a pair of
+    /// utf-16 surrogates
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        let high = self.hex4()?;
+        if !(0xd800..0xdc00).contains(&high) {
+            let c = char::from_u32(high);
+            return c.ok_or_else(|| self.error("Unpaired surrogate in \\u escape"));
+        }
+
+        if self.advance() != Some('\\') || self.advance() != Some('u') {
+            return Err(self.error("Unpaired surrogate in \\u escape"));
+        }
+        let low = self.hex4()?;
+        if !(0xdc00..0xe000).contains(&low) {
+            return Err(self.error("Unpaired surrogate in \\u escape"));
+        }
+        Ok(char::from_u32(0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)).unwrap())
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        let mut n = 0;
+        for _ in 0..4 {
+            match self.advance().and_then(|c| c.to_digit(16)) {
+                Some(digit) => n = n * 16 + digit,
+                None => return Err(self.error("Expect 4 hex digits in \\u escape")),
+            }
+        }
+        Ok(n)
+    }
+
+    /// integers become ints, anything with a fraction or exponent a float
+    fn number(&mut self) -> Result<Value, String> {
+        let start = self.current;
+        let digits = |reader: &mut Reader| {
+            let mut any = false;
+            while matches!(reader.peek(), Some('0'..='9')) {
+                reader.advance();
+                any = true;
+            }
+            any
+        };
+
+        if self.peek() == Some('-') {
+            self.advance();
+        }
+        if self.peek() == Some('0') {
+            self.advance();
+            if matches!(self.peek(), Some('0'..='9')) {
+                return Err(self.error("Leading zeros are not allowed"));
+            }
+        } else if !digits(self) {
+            return Err(self.error("Expect digit"));
+        }
+
+        let mut float = false;
+        if self.peek() == Some('.') {
+            self.advance();
+            float = true;
+            if !digits(self) {
+                return Err(self.error("Expect digit after '.'"));
+            }
+        }
+        if matches!(self.peek(), Some('e' | 'E')) {
+            self.advance();
+            float = true;
+            if matches!(self.peek(), Some('+' | '-')) {
+                self.advance();
+            }
+            if !digits(self) {
+                return Err(self.error("Expect digit in exponent"));
+            }
+        }
+
+        let text: String = self.chars[start..self.current].iter().collect();
+        if float {
+            Ok(Value::Number(text.parse().unwrap()))
+        } else {
+            Ok(Value::from(text.parse::<BigInt>().unwrap()))
+        }
+    }
+
+    fn literal(&mut self) -> Result<Value, String> {
+        let (line, column, start) = (self.line, self.column, self.current);
+        while matches!(self.peek(), Some(c) if c.is_ascii_alphanumeric()) {
+            self.advance();
+        }
+
+        let word: String = self.chars[start..self.current].iter().collect();
+        match word.as_str() {
+            "true" => Ok(Value::Bool(true)),
+            "false" => Ok(Value::Bool(false)),
+            "null" => Ok(Value::Nil),
+            _ => {
+                (self.line, self.column) = (line, column);
+                Err(self.error(&format!("Unexpected word '{}'", word)))
+            }
+        }
+    }
+}
+
+struct Writer {
+    out: String,
+    indent: Option<usize>,
+    path: Vec<*const ()>, // lists and maps being written, to catch cycles
+}
+
+impl Writer {
+    fn value(&mut self, value: &Value, depth: usize) -> Result<(), String> {
+        match value {
+            Value::Nil => self.out += "null",
+            Value::Bool(b) => self.out += &b.to_string(),
+            Value::Int(n) => self.out += &n.to_string(),
+            Value::BigInt(n) => self.out += &n.to_string(),
+            Value::Number(x) if x.is_finite() => self.out += &format!("{:?}", x),
+            Value::Number(x) => return Err(format!("Cannot stringify {}", x)),
+            Value::String(s) => self.string(s),
+            Value::List(list) => {
+                self.enter(list.as_ptr() as *const ())?;
+                let items = list.borrow();
+                self.out.push('[');
+                for (i, item) in items.iter().enumerate() {
+                    self.separator(i, depth + 1);
+                    self.value(item, depth + 1)?;
+                }
+                self.close(']', items.is_empty(), depth);
+                self.path.pop();
+            }
+            Value::Map(map) => {
+                self.enter(map.as_ptr() as *const ())?;
+                let entries = map.borrow();
+                self.out.push('{');
+                for (i, (key, item)) in entries.iter().enumerate() {
+                    self.separator(i, depth + 1);
+                    self.string(key);
+                    self.out += if self.indent.is_some() { ": " } else { ":" };
+                    self.value(item, depth + 1)?;
+                }
+                self.close('}', entries.is_empty(), depth);
+                self.path.pop();
+            }
+            value => return Err(format!("Cannot stringify a {}", value.type_name())),
+        }
+        Ok(())
+    }
+
+    fn enter(&mut self, ptr: *const ()) -> Result<(), String> {
+        if self.path.contains(&ptr) {
+            return Err(String::from(
+                "Cannot stringify a value that contains itself",
+            ));
+        }
+        self.path.push(ptr);
+        Ok(())
+    }
+
+    /// the comma before an item, and its line when pretty printing
+    fn separator(&mut self, i: usize, depth: usize) {
+        if i > 0 {
+            self.out.push(',');
+        }
+        self.newline(depth);
+    }
+
+    fn close(&mut self, bracket: char, empty: bool, depth: usize) {
+        if !empty {
+            self.newline(depth);
+        }
+        self.out.push(bracket);
+    }
+
+    fn newline(&mut self, depth: usize) {
+        if let Some(indent) = self.indent {
+            self.out.push('\n');
+            self.out += &" ".repeat(indent * depth);
+        }
+    }
+
+    fn string(&mut self, s: &str) {
+        self.out.push('"');
+        for c in s.chars() {
+            match c {
+                '"' => self.out += "\\\"",
+                '\\' => self.out += "\\\\",
+                '\n' => self.out += "\\n",
+                '\r' => self.out += "\\r",
+                '\t' => self.out += "\\t",
+                c if c < ' ' => self.out += &format!("\\u{:04x}", c as u32),
+                c => self.out.push(c),
+            }
+        }
+        self.out.push('"');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(text: &str) -> Result<Value, String> {
+        parse(&mut Vm::new(), &[Value::String(String::from(text))])
+    }
+
+    fn write(value: Value, indent: Option<i64>) -> String {
+        let mut args = vec![value];
+        args.extend(indent.map(Value::Int));
+        match stringify(&mut Vm::new(), &args) {
+            Ok(Value::String(s)) => s,
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn round_trips_documents() {
+        let text = r#"{"a":[1,2.5,-0.25,true,null],"b":{"c":"x\"y\né"},"n":12345678901234567890}"#;
+        assert_eq!(write(read(text).unwrap(), None), text);
+        assert_eq!(
+            write(read("[1.0, 0.1, -3e2]").unwrap(), None),
+            "[1.0,0.1,-300.0]"
+        );
+        assert_eq!(read(r#""😀""#).unwrap().to_string(), "😀");
+    }
+
+    #[test]
+    fn pretty_prints_with_indent() {
+        let value = read(r#"{"a": [1, {}], "b": []}"#).unwrap();
+        assert_eq!(
+            write(value, Some(2)),
+            "{\n  \"a\": [\n    1,\n    {}\n  ],\n  \"b\": []\n}"
+        );
+    }
+
+    #[test]
+    fn reports_error_positions() {
+        assert_eq!(
+            read("{\n  \"a\": 1,\n  \"b\" 2\n}").unwrap_err(),
+            "Invalid JSON at line 3, column 7: Expect ':' after key"
+        );
+        let at = |text: &str| {
+            read(text)
+                .unwrap_err()
+                .split(": ")
+                .next()
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(at("[1, 2"), "Invalid JSON at line 1, column 6");
+        assert_eq!(at("[tru]"), "Invalid JSON at line 1, column 2");
+        assert_eq!(at("012"), "Invalid JSON at line 1, column 2");
+        assert!(read(&"[".repeat(1000))
+            .unwrap_err()
+            .contains("Too deeply nested"));
+    }
+
+    #[test]
+    fn refuses_cycles() {
+        let list = Value::from(vec![Value::Int(1)]);
+        if let Value::List(items) = &list {
+            items.borrow_mut().push(list.clone());
+        }
+        let err = stringify(&mut Vm::new(), std::slice::from_ref(&list)).unwrap_err();
+        assert_eq!(err, "Cannot stringify a value that contains itself");
+        if let Value::List(items) = &list {
+            items.borrow_mut().clear(); // break the cycle so the list is freed
+        }
+
+        // the same list twice is fine when it doesn't contain itself
+        let shared = Value::from(vec![Value::Int(1)]);
+        assert_eq!(
+            write(Value::from(vec![shared.clone(), shared]), None),
+            "[[1],[1]]"
+        );
+    }
+}
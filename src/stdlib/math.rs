@@ -0,0 +1,189 @@
+use crate::bigint::BigInt;
+use crate::native::{arity, number_arg};
+use crate::value::Value;
+use crate::vm::{compare, Vm};
+
+use std::cmp::Ordering;
+use std::f64::consts;
+
+pub fn module() -> Value {
+    let module = super::module(&[
+        ("sqrt", sqrt),
+        ("pow", pow),
+        ("floor", floor),
+        ("ceil", ceil),
+        ("round", round),
+        ("abs", abs),
+        ("min", min),
+        ("max", max),
+        ("sin", sin),
+        ("cos", cos),
+        ("tan", tan),
+        ("asin", asin),
+        ("acos", acos),
+        ("atan", atan),
+        ("atan2", atan2),
+        ("exp", exp),
+        ("log", log),
+        ("log2", log2),
+        ("log10", log10),
+        ("is_nan", is_nan),
+        ("is_inf", is_inf),
+    ]);
+
+    if let Value::Map(map) = &module {
+        let mut map = map.borrow_mut();
+        map.insert(String::from("pi"), Value::Number(consts::PI));
+        map.insert(String::from("e"), Value::Number(consts::E));
+        map.insert(String::from("inf"), Value::Number(f64::INFINITY));
+        map.insert(String::from("nan"), Value::Number(f64::NAN));
+    }
+    module
+}
+
+/// applies a float function to a single numeric argument
+fn unary(name: &str, args: &[Value], f: fn(f64) -> f64) -> Result<Value, String> {
+    arity(name, args, 1)?;
+    Ok(Value::Number(f(number_arg(name, args, 0)?)))
+}
+
+/// rounds a float to an int with `f`, ints are already whole
+fn to_int(name: &str, args: &[Value], f: fn(f64) -> f64) -> Result<Value, String> {
+    arity(name, args, 1)?;
+    match &args[0] {
+        Value::Int(_) | Value::BigInt(_) => Ok(args[0].clone()),
+        _ => {
+            let x = number_arg(name, args, 0)?;
+            match BigInt::from_f64(f(x)) {
+                Some(n) => Ok(Value::from(n)),
+                None => Err(format!("Cannot convert {} to int", x)),
+            }
+        }
+    }
+}
+
+fn sqrt(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    unary("sqrt", args, f64::sqrt)
+}
+
+/// float power, unlike `**` which keeps ints exact
+fn pow(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    arity("pow", args, 2)?;
+    let base = number_arg("pow", args, 0)?;
+    Ok(Value::Number(base.powf(number_arg("pow", args, 1)?)))
+}
+
+fn floor(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    to_int("floor", args, f64::floor)
+}
+
+fn ceil(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    to_int("ceil", args, f64::ceil)
+}
+
+/// rounds halfway cases away from zero
+fn round(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    to_int("round", args, f64::round)
+}
+
+fn abs(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    arity("abs", args, 1)?;
+    match &args[0] {
+        Value::Int(n) => Ok(match n.checked_abs() {
+            Some(n) => Value::Int(n),
+            None => Value::from(-&BigInt::from(*n)),
+        }),
+        Value::BigInt(n) if n.is_negative() => Ok(Value::from(-n)),
+        Value::BigInt(_) => Ok(args[0].clone()),
+        _ => unary("abs", args, f64::abs),
+    }
+}
+
+/// the first of one or more numbers that no other comes before, nan if any
+/// of them is nan
+fn extreme(name: &str, args: &[Value], before: Ordering) -> Result<Value, String> {
+    if args.is_empty() {
+        return Err(format!("{}() expects at least 1 argument but got 0", name));
+    }
+
+    let mut best = &args[0];
+    for i in 0..args.len() {
+        number_arg(name, args, i)?;
+        match compare(&args[i], best) {
+            Some(order) if order == before => best = &args[i],
+            Some(_) => (),
+            None => return Ok(Value::Number(f64::NAN)),
+        }
+    }
+    Ok(best.clone())
+}
+
+fn min(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    extreme("min", args, Ordering::Less)
+}
+
+fn max(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    extreme("max", args, Ordering::Greater)
+}
+
+fn sin(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    unary("sin", args, f64::sin)
+}
+
+fn cos(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    unary("cos", args, f64::cos)
+}
+
+fn tan(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    unary("tan", args, f64::tan)
+}
+
+fn asin(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    unary("asin", args, f64::asin)
+}
+
+fn acos(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    unary("acos", args, f64::acos)
+}
+
+fn atan(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    unary("atan", args, f64::atan)
+}
+
+/// the angle of the point (x, y), called as `atan2(y, x)`
+fn atan2(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    arity("atan2", args, 2)?;
+    let y = number_arg("atan2", args, 0)?;
+    Ok(Value::Number(y.atan2(number_arg("atan2", args, 1)?)))
+}
+
+fn exp(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    unary("exp", args, f64::exp)
+}
+
+/// natural logarithm, or the logarithm in a base given as a second argument
+fn log(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    if args.len() == 2 {
+        let x = number_arg("log", args, 0)?;
+        return Ok(Value::Number(x.log(number_arg("log", args, 1)?)));
+    }
+    unary("log", args, f64::ln)
+}
+
+fn log2(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    unary("log2", args, f64::log2)
+}
+
+fn log10(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    unary("log10", args, f64::log10)
+}
+
+fn is_nan(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    arity("is_nan", args, 1)?;
+    Ok(Value::Bool(number_arg("is_nan", args, 0)?.is_nan()))
+}
+
+fn is_inf(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    arity("is_inf", args, 1)?;
+    Ok(Value::Bool(number_arg("is_inf", args, 0)?.is_infinite()))
+}
@@ -0,0 +1,49 @@
+use crate::function::{Function, NativeFn};
+use crate::value::Value;
+use crate::vm::Vm;
+
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+pub mod fs;
+pub mod io;
+pub mod json;
+pub mod math;
+pub mod random;
+pub mod string;
+pub mod time;
+
+/// modules written in rust, imported by name like `import fs`
+pub fn define_modules(vm: &mut Vm) {
+    vm.define_module("fs", fs::module());
+    vm.define_module("io", io::module());
+    vm.define_module("json", json::module());
+    vm.define_module("math", math::module());
+    vm.define_module("random", random::module());
+    vm.define_module("time", time::module());
+
+    // timing code is common enough to not need an import
+    vm.define_native("clock", time::clock);
+}
+
+/// a native module is a map of its functions, like the exports of a file
+fn module(functions: &[(&str, NativeFn)]) -> Value {
+    let exports: BTreeMap<String, Value> = functions
+        .iter()
+        .map(|(name, f)| {
+            let f = Function::native(name, *f);
+            (String::from(*name), Value::Function(Rc::new(f)))
+        })
+        .collect();
+    Value::from(exports)
+}
+
+/// io errors read like "No such file or directory", without the os error code
+fn io_error(action: &str, path: &str, error: std::io::Error) -> String {
+    let msg = error.to_string();
+    let msg = match msg.find(" (os error") {
+        Some(end) => &msg[..end],
+        None => &msg,
+    };
+    format!("Cannot {} '{}': {}", action, path, msg)
+}
@@ -0,0 +1,197 @@
+use crate::native::{arity, int_arg, number_arg};
+use crate::value::Value;
+use crate::vm::Vm;
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// xoshiro256** generator. The same seed gives the same numbers on every
+/// machine, so seeded scripts are reproducible
+#[derive(Debug)]
+pub struct Rng {
+    state: [u64; 4],
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        // splitmix64 spreads the seed over the whole state, which must not be all zeros
+        let mut x = seed;
+        let mut next = || {
+            x = x.wrapping_add(0x9e3779b97f4a7c15);
+            let mut z = x;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+            z ^ (z >> 31)
+        };
+        Rng {
+            state: [next(), next(), next(), next()],
+        }
+    }
+
+    /// seeded from the clock, for scripts that don't ask for a seed
+    pub fn from_time() -> Rng {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        Rng::new(now.as_nanos() as u64 ^ ((std::process::id() as u64) << 32))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let s = &mut self.state;
+        let result = s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = s[1] << 17;
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+        result
+    }
+
+    /// uniform in [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// uniform in [low, high], both inclusive
+    pub fn range(&mut self, low: i64, high: i64) -> i64 {
+        let span = (high as i128 - low as i128 + 1) as u128;
+        if span > u64::MAX as u128 {
+            return self.next_u64() as i64;
+        }
+
+        // reject the top values that would make some results more likely
+        let span = span as u64;
+        let limit = u64::MAX - u64::MAX % span;
+        loop {
+            let x = self.next_u64();
+            if x < limit {
+                return (low as i128 + (x % span) as i128) as i64;
+            }
+        }
+    }
+}
+
+pub fn module() -> Value {
+    super::module(&[
+        ("seed", seed),
+        ("random", random),
+        ("int", int),
+        ("float", float),
+        ("choice", choice),
+        ("shuffle", shuffle),
+    ])
+}
+
+/// restarts the generator, making the numbers after it reproducible
+fn seed(vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    arity("seed", args, 1)?;
+    vm.seed_random(int_arg("seed", args, 0)? as u64);
+    Ok(Value::Nil)
+}
+
+/// a float in [0, 1)
+fn random(vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    arity("random", args, 0)?;
+    Ok(Value::Number(vm.rng().next_f64()))
+}
+
+/// an int between two bounds, both included
+fn int(vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    arity("int", args, 2)?;
+    let low = int_arg("int", args, 0)?;
+    let high = int_arg("int", args, 1)?;
+    if low > high {
+        return Err(format!(
+            "int() expects low <= high but got {} and {}",
+            low, high
+        ));
+    }
+    Ok(Value::Int(vm.rng().range(low, high)))
+}
+
+/// a float in [low, high)
+fn float(vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    arity("float", args, 2)?;
+    let low = number_arg("float", args, 0)?;
+    let high = number_arg("float", args, 1)?;
+    if low > high {
+        return Err(format!(
+            "float() expects low <= high but got {} and {}",
+            low, high
+        ));
+    }
+    Ok(Value::Number(low + (high - low) * vm.rng().next_f64()))
+}
+
+/// a random item of a non-empty list
+fn choice(vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    arity("choice", args, 1)?;
+    let list = match &args[0] {
+        Value::List(list) => list.borrow(),
+        value => {
+            return Err(format!(
+                "choice() expects a list but got {}",
+                value.type_name()
+            ))
+        }
+    };
+    if list.is_empty() {
+        return Err(String::from("Cannot choose from an empty list"));
+    }
+    let i = vm.rng().range(0, list.len() as i64 - 1);
+    Ok(list[i as usize].clone())
+}
+
+/// shuffles a list in place
+fn shuffle(vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    arity("shuffle", args, 1)?;
+    let mut list = match &args[0] {
+        Value::List(list) => list.borrow_mut(),
+        value => {
+            return Err(format!(
+                "shuffle() expects a list but got {}",
+                value.type_name()
+            ))
+        }
+    };
+    // fisher-yates
+    for i in (1..list.len()).rev() {
+        let j = vm.rng().range(0, i as i64);
+        list.swap(i, j as usize);
+    }
+    Ok(Value::Nil)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_gives_same_numbers() {
+        let mut a = Rng::new(42);
+        let mut b = Rng::new(42);
+        let mut c = Rng::new(43);
+        let xs: Vec<u64> = (0..5).map(|_| a.next_u64()).collect();
+        let ys: Vec<u64> = (0..5).map(|_| b.next_u64()).collect();
+        let zs: Vec<u64> = (0..5).map(|_| c.next_u64()).collect();
+        assert_eq!(xs, ys);
+        assert_ne!(xs, zs);
+    }
+
+    #[test]
+    fn ranges_include_both_bounds() {
+        let mut rng = Rng::new(7);
+        let mut seen = [false; 3];
+        for _ in 0..100 {
+            let x = rng.range(-1, 1);
+            seen[(x + 1) as usize] = true;
+        }
+        assert_eq!(seen, [true; 3]);
+
+        rng.range(i64::MIN, i64::MAX);
+        assert!((0..100)
+            .map(|_| rng.next_f64())
+            .all(|x| (0.0..1.0).contains(&x)));
+    }
+}
@@ -0,0 +1,161 @@
+use crate::native::{arity, int_arg, string_arg};
+use crate::value::Value;
+
+/// methods of strings. Lengths and positions count characters, not bytes
+pub fn string_method(s: &str, name: &str, args: &[Value]) -> Result<Value, String> {
+    match name {
+        "len" => {
+            arity(name, args, 0)?;
+            Ok(Value::Int(s.chars().count() as i64))
+        }
+        // `s.slice(start, end)` takes the characters from start up to end,
+        // which default to the ends of the string. Negative positions count
+        // from the end and positions past either end are clamped
+        "slice" => {
+            if args.is_empty() || args.len() > 2 {
+                return Err(format!(
+                    "slice() expects 1 or 2 argument(s) but got {}",
+                    args.len()
+                ));
+            }
+            let len = s.chars().count();
+            let start = clamp(int_arg(name, args, 0)?, len);
+            let end = match args.get(1) {
+                Some(_) => clamp(int_arg(name, args, 1)?, len),
+                None => len,
+            };
+            let slice = s
+                .chars()
+                .skip(start)
+                .take(end.saturating_sub(start))
+                .collect();
+            Ok(Value::String(slice))
+        }
+        // splits on a separator, or on runs of whitespace without one
+        "split" => {
+            let parts: Vec<&str> = match args {
+                [] => s.split_whitespace().collect(),
+                [_] => {
+                    let sep = string_arg(name, args, 0)?;
+                    if sep.is_empty() {
+                        return Err(String::from("split() separator cannot be empty"));
+                    }
+                    s.split(sep).collect()
+                }
+                _ => {
+                    return Err(format!(
+                        "split() expects 0 or 1 argument(s) but got {}",
+                        args.len()
+                    ))
+                }
+            };
+            Ok(strings(parts))
+        }
+        // `', '.join(items)` puts the string between the items
+        "join" => {
+            arity(name, args, 1)?;
+            match &args[0] {
+                Value::List(items) => {
+                    let items: Vec<String> = items.borrow().iter().map(|v| v.to_string()).collect();
+                    Ok(Value::String(items.join(s)))
+                }
+                value => Err(format!(
+                    "join() expects a list but got {}",
+                    value.type_name()
+                )),
+            }
+        }
+        "trim" => {
+            arity(name, args, 0)?;
+            Ok(Value::String(String::from(s.trim())))
+        }
+        "trim_start" => {
+            arity(name, args, 0)?;
+            Ok(Value::String(String::from(s.trim_start())))
+        }
+        "trim_end" => {
+            arity(name, args, 0)?;
+            Ok(Value::String(String::from(s.trim_end())))
+        }
+        "upper" => {
+            arity(name, args, 0)?;
+            Ok(Value::String(s.to_uppercase()))
+        }
+        "lower" => {
+            arity(name, args, 0)?;
+            Ok(Value::String(s.to_lowercase()))
+        }
+        "replace" => {
+            arity(name, args, 2)?;
+            let from = string_arg(name, args, 0)?;
+            if from.is_empty() {
+                return Err(String::from("replace() pattern cannot be empty"));
+            }
+            Ok(Value::String(s.replace(from, string_arg(name, args, 1)?)))
+        }
+        // position of the first occurrence, nil when there is none
+        "find" => {
+            arity(name, args, 1)?;
+            let index = s
+                .find(string_arg(name, args, 0)?)
+                .map_or(Value::Nil, |i| Value::Int(s[..i].chars().count() as i64));
+            Ok(index)
+        }
+        "contains" => {
+            arity(name, args, 1)?;
+            Ok(Value::Bool(s.contains(string_arg(name, args, 0)?)))
+        }
+        "starts_with" => {
+            arity(name, args, 1)?;
+            Ok(Value::Bool(s.starts_with(string_arg(name, args, 0)?)))
+        }
+        "ends_with" => {
+            arity(name, args, 1)?;
+            Ok(Value::Bool(s.ends_with(string_arg(name, args, 0)?)))
+        }
+        "repeat" => {
+            arity(name, args, 1)?;
+            let count = int_arg(name, args, 0)?;
+            if count < 0 {
+                return Err(format!(
+                    "repeat() count cannot be negative but got {}",
+                    count
+                ));
+            }
+            Ok(Value::String(s.repeat(count as usize)))
+        }
+        // a list of the characters, each as a string
+        "chars" => {
+            arity(name, args, 0)?;
+            Ok(Value::from(
+                s.chars()
+                    .map(|c| Value::String(c.to_string()))
+                    .collect::<Vec<_>>(),
+            ))
+        }
+        // the utf-8 bytes as ints
+        "bytes" => {
+            arity(name, args, 0)?;
+            Ok(Value::from(
+                s.bytes().map(|b| Value::Int(b as i64)).collect::<Vec<_>>(),
+            ))
+        }
+        _ => Err(format!("Undefined method '{}' on string", name)),
+    }
+}
+
+/// a position in a string of `len` characters, counting back from the end
+/// when negative
+fn clamp(index: i64, len: usize) -> usize {
+    let index = if index < 0 { index + len as i64 } else { index };
+    index.clamp(0, len as i64) as usize
+}
+
+fn strings(parts: Vec<&str>) -> Value {
+    Value::from(
+        parts
+            .into_iter()
+            .map(|part| Value::String(String::from(part)))
+            .collect::<Vec<_>>(),
+    )
+}
@@ -0,0 +1,447 @@
+use crate::native::{arity, number_arg, string_arg};
+use crate::value::Value;
+use crate::vm::Vm;
+
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// timestamps are seconds since the unix epoch and durations are seconds,
+/// both floats, so they can be added and subtracted like any other number
+pub fn module() -> Value {
+    super::module(&[
+        ("now", now),
+        ("monotonic", monotonic),
+        ("sleep", sleep),
+        ("seconds", seconds),
+        ("minutes", minutes),
+        ("hours", hours),
+        ("days", days),
+        ("format", format),
+        ("parse", parse),
+        ("format_duration", format_duration),
+        ("parse_duration", parse_duration),
+    ])
+}
+
+/// seconds from a clock that never goes back, for timing code by taking
+/// the difference of two readings
+fn monotonic(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    arity("monotonic", args, 0)?;
+    Ok(elapsed())
+}
+
+/// the global `clock()`, the same clock as `time.monotonic()`
+pub fn clock(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    arity("clock", args, 0)?;
+    Ok(elapsed())
+}
+
+fn elapsed() -> Value {
+    static START: OnceLock<Instant> = OnceLock::new();
+    Value::Number(START.get_or_init(Instant::now).elapsed().as_secs_f64())
+}
+
+/// the wall clock time as a timestamp
+fn now(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    arity("now", args, 0)?;
+    let since_epoch = match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_secs_f64(),
+        Err(e) => -e.duration().as_secs_f64(),
+    };
+    Ok(Value::Number(since_epoch))
+}
+
+fn sleep(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    arity("sleep", args, 1)?;
+    let seconds = number_arg("sleep", args, 0)?;
+    match Duration::try_from_secs_f64(seconds) {
+        Ok(duration) => thread::sleep(duration),
+        Err(_) => return Err(format!("Cannot sleep for {} seconds", seconds)),
+    }
+    Ok(Value::Nil)
+}
+
+fn scaled(name: &str, args: &[Value], factor: f64) -> Result<Value, String> {
+    arity(name, args, 1)?;
+    Ok(Value::Number(number_arg(name, args, 0)? * factor))
+}
+
+fn seconds(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    scaled("seconds", args, 1.0)
+}
+
+fn minutes(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    scaled("minutes", args, 60.0)
+}
+
+fn hours(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    scaled("hours", args, 3600.0)
+}
+
+fn days(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    scaled("days", args, 86400.0)
+}
+
+/// days since 1970-01-01 of a date in the proleptic gregorian calendar
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// the year, month and day of a number of days since 1970-01-01
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    (year, month, day)
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// a utc offset in seconds from "Z", "+hh:mm", "+hhmm" or "+hh"
+fn parse_offset(offset: &str) -> Option<i64> {
+    if offset == "Z" || offset == "z" {
+        return Some(0);
+    }
+
+    let sign = match offset.chars().next()? {
+        '+' => 1,
+        '-' => -1,
+        _ => return None,
+    };
+    let digits = offset[1..].replacen(':', "", 1);
+    if !matches!(digits.len(), 2 | 4) || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    let hours: i64 = digits[..2].parse().ok()?;
+    let minutes: i64 = if digits.len() == 4 {
+        digits[2..].parse().ok()?
+    } else {
+        0
+    };
+    if hours > 23 || minutes > 59 {
+        return None;
+    }
+    Some(sign * (hours * 3600 + minutes * 60))
+}
+
+/// writes a timestamp as an ISO-8601 date and time, in utc unless given
+/// an offset like "+05:30". Milliseconds are only shown when there are some
+fn format(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    let offset = match args.len() {
+        1 => 0,
+        2 => {
+            let offset = string_arg("format", args, 1)?;
+            parse_offset(offset).ok_or_else(|| format!("Invalid UTC offset '{}'", offset))?
+        }
+        n => return Err(format!("format() expects 1 or 2 argument(s) but got {}", n)),
+    };
+
+    let timestamp = number_arg("format", args, 0)?;
+    let millis = (timestamp * 1000.0).round();
+    if !millis.is_finite() || millis.abs() > 2.5e17 {
+        return Err(format!("Cannot format timestamp {}", timestamp));
+    }
+
+    let millis = millis as i64 + offset * 1000;
+    let seconds = millis.div_euclid(1000);
+    let (year, month, day) = civil_from_days(seconds.div_euclid(86400));
+    if !(0..=9999).contains(&year) {
+        return Err(format!("Year {} is outside ISO-8601's 0000 to 9999", year));
+    }
+
+    let time = seconds.rem_euclid(86400);
+    let mut s = format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    );
+    if millis.rem_euclid(1000) != 0 {
+        s += &format!(".{:03}", millis.rem_euclid(1000));
+    }
+
+    if offset == 0 {
+        s.push('Z');
+    } else {
+        let sign = if offset < 0 { '-' } else { '+' };
+        s += &format!(
+            "{}{:02}:{:02}",
+            sign,
+            offset.abs() / 3600,
+            offset.abs() / 60 % 60
+        );
+    }
+    Ok(Value::String(s))
+}
+
+/// reads an ISO-8601 date, or date and time, into a timestamp. Times
+/// without an offset are utc
+fn parse(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    arity("parse", args, 1)?;
+    let text = string_arg("parse", args, 0)?;
+    let invalid = || format!("Invalid ISO-8601 date '{}'", text);
+
+    let number = |s: Option<&str>| -> Result<i64, String> {
+        match s {
+            Some(s) if !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()) => {
+                s.parse().map_err(|_| invalid())
+            }
+            _ => Err(invalid()),
+        }
+    };
+
+    // yyyy-mm-dd
+    let date = text.get(..10).ok_or_else(invalid)?;
+    if date.as_bytes()[4] != b'-' || date.as_bytes()[7] != b'-' {
+        return Err(invalid());
+    }
+    let year = number(date.get(..4))?;
+    let month = number(date.get(5..7))?;
+    let day = number(date.get(8..10))?;
+    if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+        return Err(invalid());
+    }
+    let mut timestamp = (days_from_civil(year, month, day) * 86400) as f64;
+
+    let rest = &text[10..];
+    if rest.is_empty() {
+        return Ok(Value::Number(timestamp));
+    }
+
+    // Thh:mm[:ss[.fff]]
+    let rest = rest.strip_prefix(['T', 't', ' ']).ok_or_else(invalid)?;
+    let end = rest.find(['Z', 'z', '+', '-']).unwrap_or(rest.len());
+    let (time, offset) = rest.split_at(end);
+    let (time, fraction) = match time.split_once('.') {
+        Some((time, fraction)) => (time, Some(fraction)),
+        None => (time, None),
+    };
+
+    let mut parts = time.split(':');
+    let hour = number(parts.next())?;
+    let minute = number(parts.next())?;
+    let second = match parts.next() {
+        Some(s) => number(Some(s))?,
+        None if fraction.is_none() => 0,
+        None => return Err(invalid()),
+    };
+    if parts.next().is_some() || time.len() != 5 && time.len() != 8 {
+        return Err(invalid());
+    }
+    if hour > 23 || minute > 59 || second > 59 {
+        return Err(invalid());
+    }
+    timestamp += (hour * 3600 + minute * 60 + second) as f64;
+
+    if let Some(fraction) = fraction {
+        number(Some(fraction))?;
+        timestamp += format!("0.{}", fraction)
+            .parse::<f64>()
+            .map_err(|_| invalid())?;
+    }
+
+    if !offset.is_empty() {
+        timestamp -= parse_offset(offset).ok_or_else(invalid)? as f64;
+    }
+    Ok(Value::Number(timestamp))
+}
+
+/// writes seconds as an ISO-8601 duration like "P1DT2H30M"
+fn format_duration(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    arity("format_duration", args, 1)?;
+    let seconds = number_arg("format_duration", args, 0)?;
+    if !seconds.is_finite() {
+        return Err(format!("Cannot format duration {}", seconds));
+    }
+
+    let total = (seconds.abs() * 1000.0).round() as u64;
+    let (days, millis) = (total / 86_400_000, total % 86_400_000);
+    let (hours, millis) = (millis / 3_600_000, millis % 3_600_000);
+    let (minutes, millis) = (millis / 60_000, millis % 60_000);
+
+    let mut s = String::from(if seconds < 0.0 && total > 0 {
+        "-P"
+    } else {
+        "P"
+    });
+    if days > 0 {
+        s += &format!("{}D", days);
+    }
+    if hours + minutes + millis > 0 || days == 0 {
+        s.push('T');
+    }
+    if hours > 0 {
+        s += &format!("{}H", hours);
+    }
+    if minutes > 0 {
+        s += &format!("{}M", minutes);
+    }
+    if millis % 1000 != 0 {
+        let fraction = format!("{:03}", millis % 1000);
+        s += &format!("{}.{}S", millis / 1000, fraction.trim_end_matches('0'));
+    } else if millis > 0 || s.ends_with('T') {
+        s += &format!("{}S", millis / 1000);
+    }
+    Ok(Value::String(s))
+}
+
+/// reads an ISO-8601 duration in weeks, days, hours, minutes and seconds
+/// into seconds. Years and months have no fixed length so aren't allowed
+fn parse_duration(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    arity("parse_duration", args, 1)?;
+    let text = string_arg("parse_duration", args, 0)?;
+    let invalid = || format!("Invalid ISO-8601 duration '{}'", text);
+
+    let (sign, rest) = match text.strip_prefix('-') {
+        Some(rest) => (-1.0, rest),
+        None => (1.0, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let rest = rest.strip_prefix('P').ok_or_else(invalid)?;
+    let (date, time) = match rest.split_once('T') {
+        Some((_, "")) => return Err(invalid()),
+        Some((date, time)) => (date, time),
+        None => (rest, ""),
+    };
+
+    // each part is a number followed by its unit, in this order
+    let date_units = [('W', 604800.0), ('D', 86400.0)];
+    let time_units = [('H', 3600.0), ('M', 60.0), ('S', 1.0)];
+    let mut total = 0.0;
+    let mut parts = 0;
+    let sections = [
+        (date, &date_units[..], true),
+        (time, &time_units[..], false),
+    ];
+    for (mut part, units, is_date) in sections {
+        let mut units = units.iter();
+        while !part.is_empty() {
+            let end = part
+                .find(|c: char| !c.is_ascii_digit() && c != '.')
+                .ok_or_else(invalid)?;
+            let (number, unit) = (&part[..end], part[end..].chars().next().unwrap());
+            if unit == 'Y' || unit == 'M' && is_date {
+                let msg = "has years or months, which have no fixed length";
+                return Err(format!("Duration '{}' {}", text, msg));
+            }
+
+            let &(_, scale) = units.find(|(u, _)| *u == unit).ok_or_else(invalid)?;
+            if number.starts_with('.') || number.ends_with('.') {
+                return Err(invalid());
+            }
+            let value: f64 = number.parse().map_err(|_| invalid())?;
+            total += value * scale;
+            parts += 1;
+            part = &part[end + unit.len_utf8()..];
+        }
+    }
+
+    if parts == 0 {
+        return Err(invalid());
+    }
+    Ok(Value::Number(sign * total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::function::NativeFn;
+
+    fn call(f: NativeFn, args: &[Value]) -> Result<String, String> {
+        f(&mut Vm::new(), args).map(|v| v.to_string())
+    }
+
+    fn s(text: &str) -> Value {
+        Value::String(String::from(text))
+    }
+
+    #[test]
+    fn converts_between_days_and_dates() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(2000, 3, 1), 11017);
+        assert_eq!(civil_from_days(11017), (2000, 3, 1));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        for days in [-719468, -1, 0, 59, 10957, 19782, 2932896] {
+            let (y, m, d) = civil_from_days(days);
+            assert_eq!(days_from_civil(y, m, d), days);
+        }
+    }
+
+    #[test]
+    fn formats_and_parses_timestamps() {
+        let ts = Value::Number(1709294400.25);
+        assert_eq!(
+            call(format, std::slice::from_ref(&ts)).unwrap(),
+            "2024-03-01T12:00:00.250Z"
+        );
+        assert_eq!(
+            call(format, &[ts, s("-05:00")]).unwrap(),
+            "2024-03-01T07:00:00.250-05:00"
+        );
+
+        assert_eq!(
+            call(parse, &[s("2024-03-01T07:00:00.25-05:00")]).unwrap(),
+            "1709294400.25"
+        );
+        assert_eq!(call(parse, &[s("1970-01-01")]).unwrap(), "0");
+        assert_eq!(
+            call(parse, &[s("2024-02-29T23:59+0130")]).unwrap(),
+            "1709245740"
+        );
+        assert!(call(parse, &[s("2023-02-29")]).is_err());
+        assert!(call(parse, &[s("2024-03-01T24:00")]).is_err());
+        assert!(call(parse, &[s("2024-03-01T12:00+25:00")]).is_err());
+    }
+
+    #[test]
+    fn formats_and_parses_durations() {
+        assert_eq!(
+            call(format_duration, &[Value::Number(95400.0)]).unwrap(),
+            "P1DT2H30M"
+        );
+        assert_eq!(
+            call(format_duration, &[Value::Number(-1.5)]).unwrap(),
+            "-PT1.5S"
+        );
+        assert_eq!(
+            call(format_duration, &[Value::Number(0.0)]).unwrap(),
+            "PT0S"
+        );
+        assert_eq!(
+            call(format_duration, &[Value::Number(86400.0)]).unwrap(),
+            "P1D"
+        );
+
+        assert_eq!(call(parse_duration, &[s("P1DT2H30M")]).unwrap(), "95400");
+        assert_eq!(call(parse_duration, &[s("-PT1.5S")]).unwrap(), "-1.5");
+        assert_eq!(call(parse_duration, &[s("P2W")]).unwrap(), "1209600");
+        assert!(call(parse_duration, &[s("P1M")])
+            .unwrap_err()
+            .contains("no fixed length"));
+        assert!(call(parse_duration, &[s("PT")]).is_err());
+        assert!(call(parse_duration, &[s("PT1S2M")]).is_err());
+    }
+}
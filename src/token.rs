@@ -1,3 +1,4 @@
+use crate::bigint::BigInt;
 use crate::parse_rule::ParseRule;
 use crate::parser::Parser;
 use crate::precedence::Precedence;
@@ -11,6 +12,9 @@ pub enum TokenType {
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
+    Colon,
     Comma,
     Dot,
     Minus,
@@ -18,9 +22,11 @@ pub enum TokenType {
     Semicolon,
     Slash,
     Star,
+    StarStar,
     Mod,
     BitwiseAnd,
     BitwiseOr,
+    BitwiseXor,
 
     // 1 or 2 character tokens
     Bang,
@@ -33,31 +39,57 @@ pub enum TokenType {
     LessEqual,
     LogicalAnd,
     LogicalOr,
+    SlashSlash,
+    ShiftLeft,
+    ShiftRight,
+    FatArrow,
+    DotDot,
+    DotDotEqual,
+    PlusEqual,
+    MinusEqual,
+    StarEqual,
+    SlashEqual,
+    ModEqual,
+    AmpEqual,
+    PipeEqual,
+    Question,
 
     // Literals
     Identifier(String),
     String(String),
+    Interpolation(String), // string segment before an interpolated expression
     Number(f64),
+    Int(i64),
+    BigInt(BigInt),
 
     // Keywords
     And,
+    Catch,
     Class,
     Else,
+    Export,
     False,
+    Finally,
     For,
     Fn,
+    From,
     If,
+    Import,
+    Match,
     Nil,
     Or,
     Print,
     Return,
     Super,
     This,
+    Throw,
     True,
+    Try,
     Var,
     While,
 
     // Misc tokens
+    Newline,
     Error(String),
 }
 
@@ -94,7 +126,7 @@ impl TokenType {
                 precedence: Precedence::None,
             },
             TokenType::LeftBrace => &ParseRule {
-                prefix: None,
+                prefix: Some(Parser::block_expression),
                 infix: None,
                 precedence: Precedence::None,
             },
@@ -103,16 +135,126 @@ impl TokenType {
                 infix: None,
                 precedence: Precedence::None,
             },
-            TokenType::Comma => &ParseRule {
+            TokenType::LeftBracket => &ParseRule {
+                prefix: Some(Parser::list),
+                infix: Some(Parser::subscript),
+                precedence: Precedence::Call,
+            },
+            TokenType::RightBracket => &ParseRule {
                 prefix: None,
                 infix: None,
                 precedence: Precedence::None,
             },
-            TokenType::Dot => &ParseRule {
+            TokenType::Colon => &ParseRule {
+                prefix: None,
+                infix: None,
+                precedence: Precedence::None,
+            },
+            TokenType::FatArrow => &ParseRule {
+                prefix: None,
+                infix: None,
+                precedence: Precedence::None,
+            },
+            TokenType::DotDot => &ParseRule {
+                prefix: None,
+                infix: None,
+                precedence: Precedence::None,
+            },
+            TokenType::DotDotEqual => &ParseRule {
+                prefix: None,
+                infix: None,
+                precedence: Precedence::None,
+            },
+            TokenType::PlusEqual => &ParseRule {
+                prefix: None,
+                infix: None,
+                precedence: Precedence::None,
+            },
+            TokenType::MinusEqual => &ParseRule {
+                prefix: None,
+                infix: None,
+                precedence: Precedence::None,
+            },
+            TokenType::StarEqual => &ParseRule {
+                prefix: None,
+                infix: None,
+                precedence: Precedence::None,
+            },
+            TokenType::SlashEqual => &ParseRule {
+                prefix: None,
+                infix: None,
+                precedence: Precedence::None,
+            },
+            TokenType::ModEqual => &ParseRule {
+                prefix: None,
+                infix: None,
+                precedence: Precedence::None,
+            },
+            TokenType::AmpEqual => &ParseRule {
+                prefix: None,
+                infix: None,
+                precedence: Precedence::None,
+            },
+            TokenType::PipeEqual => &ParseRule {
+                prefix: None,
+                infix: None,
+                precedence: Precedence::None,
+            },
+            TokenType::Question => &ParseRule {
+                prefix: None,
+                infix: Some(Parser::propagate),
+                precedence: Precedence::Call,
+            },
+            TokenType::Export => &ParseRule {
+                prefix: None,
+                infix: None,
+                precedence: Precedence::None,
+            },
+            TokenType::From => &ParseRule {
+                prefix: None,
+                infix: None,
+                precedence: Precedence::None,
+            },
+            TokenType::Import => &ParseRule {
                 prefix: None,
                 infix: None,
                 precedence: Precedence::None,
             },
+            TokenType::Catch => &ParseRule {
+                prefix: None,
+                infix: None,
+                precedence: Precedence::None,
+            },
+            TokenType::Finally => &ParseRule {
+                prefix: None,
+                infix: None,
+                precedence: Precedence::None,
+            },
+            TokenType::Throw => &ParseRule {
+                prefix: Some(Parser::throw),
+                infix: None,
+                precedence: Precedence::None,
+            },
+            TokenType::Try => &ParseRule {
+                prefix: Some(Parser::try_expression),
+                infix: None,
+                precedence: Precedence::None,
+            },
+            TokenType::Match => &ParseRule {
+                prefix: Some(Parser::match_expression),
+                infix: None,
+                precedence: Precedence::None,
+            },
+            TokenType::Comma => &ParseRule {
+                prefix: None,
+                infix: None,
+                precedence: Precedence::None,
+            },
+            TokenType::Dot => &ParseRule {
+                prefix: None,
+                infix: Some(Parser::dot),
+                precedence: Precedence::Call,
+            },
             TokenType::Minus => &ParseRule {
                 prefix: Some(Parser::unary),
                 infix: Some(Parser::binary),
@@ -138,6 +280,11 @@ impl TokenType {
                 infix: Some(Parser::binary),
                 precedence: Precedence::Factor,
             },
+            TokenType::StarStar => &ParseRule {
+                prefix: None,
+                infix: Some(Parser::binary),
+                precedence: Precedence::Exponent,
+            },
             TokenType::Mod => &ParseRule {
                 prefix: None,
                 infix: Some(Parser::binary),
@@ -149,17 +296,37 @@ impl TokenType {
                 precedence: Precedence::Term,
             },
             TokenType::BitwiseOr => &ParseRule {
+                prefix: Some(Parser::arrow_lambda),
+                infix: Some(Parser::binary),
+                precedence: Precedence::Term,
+            },
+            TokenType::BitwiseXor => &ParseRule {
                 prefix: None,
                 infix: Some(Parser::binary),
                 precedence: Precedence::Term,
             },
+            TokenType::SlashSlash => &ParseRule {
+                prefix: None,
+                infix: Some(Parser::binary),
+                precedence: Precedence::Factor,
+            },
+            TokenType::ShiftLeft => &ParseRule {
+                prefix: None,
+                infix: Some(Parser::binary),
+                precedence: Precedence::Shift,
+            },
+            TokenType::ShiftRight => &ParseRule {
+                prefix: None,
+                infix: Some(Parser::binary),
+                precedence: Precedence::Shift,
+            },
             TokenType::LogicalAnd => &ParseRule {
                 prefix: None,
                 infix: Some(Parser::binary),
                 precedence: Precedence::And,
             },
             TokenType::LogicalOr => &ParseRule {
-                prefix: None,
+                prefix: Some(Parser::arrow_lambda),
                 infix: Some(Parser::binary),
                 precedence: Precedence::Or,
             },
@@ -213,11 +380,26 @@ impl TokenType {
                 infix: None,
                 precedence: Precedence::None,
             },
+            TokenType::Interpolation(_) => &ParseRule {
+                prefix: Some(Parser::interpolation),
+                infix: None,
+                precedence: Precedence::None,
+            },
             TokenType::Number(_) => &ParseRule {
                 prefix: Some(Parser::number),
                 infix: None,
                 precedence: Precedence::None,
             },
+            TokenType::Int(_) => &ParseRule {
+                prefix: Some(Parser::number),
+                infix: None,
+                precedence: Precedence::None,
+            },
+            TokenType::BigInt(_) => &ParseRule {
+                prefix: Some(Parser::number),
+                infix: None,
+                precedence: Precedence::None,
+            },
             TokenType::And => &ParseRule {
                 prefix: None,
                 infix: Some(Parser::binary),
@@ -244,12 +426,12 @@ impl TokenType {
                 precedence: Precedence::None,
             },
             TokenType::Fn => &ParseRule {
-                prefix: None,
+                prefix: Some(Parser::lambda),
                 infix: None,
                 precedence: Precedence::None,
             },
             TokenType::If => &ParseRule {
-                prefix: None,
+                prefix: Some(Parser::if_expression),
                 infix: None,
                 precedence: Precedence::None,
             },
@@ -298,6 +480,11 @@ impl TokenType {
                 infix: None,
                 precedence: Precedence::None,
             },
+            TokenType::Newline => &ParseRule {
+                prefix: None,
+                infix: None,
+                precedence: Precedence::None,
+            },
             TokenType::Error(_) => &ParseRule {
                 prefix: None,
                 infix: None,
@@ -314,6 +501,9 @@ impl Display for TokenType {
             TokenType::RightParen => write!(f, "RightParen"),
             TokenType::LeftBrace => write!(f, "LeftBrace"),
             TokenType::RightBrace => write!(f, "RightBrace"),
+            TokenType::LeftBracket => write!(f, "LeftBracket"),
+            TokenType::RightBracket => write!(f, "RightBracket"),
+            TokenType::Colon => write!(f, "Colon"),
             TokenType::Comma => write!(f, "Comma"),
             TokenType::Dot => write!(f, "Dot"),
             TokenType::Minus => write!(f, "Minus"),
@@ -321,11 +511,27 @@ impl Display for TokenType {
             TokenType::Semicolon => write!(f, "Semicolon"),
             TokenType::Slash => write!(f, "Slash"),
             TokenType::Star => write!(f, "Star"),
+            TokenType::StarStar => write!(f, "StarStar"),
             TokenType::Mod => write!(f, "Mod"),
             TokenType::BitwiseAnd => write!(f, "BitwiseAnd"),
             TokenType::BitwiseOr => write!(f, "BitwiseOr"),
+            TokenType::BitwiseXor => write!(f, "BitwiseXor"),
+            TokenType::SlashSlash => write!(f, "SlashSlash"),
+            TokenType::ShiftLeft => write!(f, "ShiftLeft"),
+            TokenType::ShiftRight => write!(f, "ShiftRight"),
             TokenType::LogicalAnd => write!(f, "LogicalAnd"),
             TokenType::LogicalOr => write!(f, "LogicalOr"),
+            TokenType::FatArrow => write!(f, "FatArrow"),
+            TokenType::DotDot => write!(f, "DotDot"),
+            TokenType::DotDotEqual => write!(f, "DotDotEqual"),
+            TokenType::PlusEqual => write!(f, "PlusEqual"),
+            TokenType::MinusEqual => write!(f, "MinusEqual"),
+            TokenType::StarEqual => write!(f, "StarEqual"),
+            TokenType::SlashEqual => write!(f, "SlashEqual"),
+            TokenType::ModEqual => write!(f, "ModEqual"),
+            TokenType::AmpEqual => write!(f, "AmpEqual"),
+            TokenType::PipeEqual => write!(f, "PipeEqual"),
+            TokenType::Question => write!(f, "Question"),
             TokenType::Bang => write!(f, "Bang"),
             TokenType::BangEqual => write!(f, "BangEqual"),
             TokenType::Equal => write!(f, "Equal"),
@@ -336,7 +542,10 @@ impl Display for TokenType {
             TokenType::LessEqual => write!(f, "LessEqual"),
             TokenType::Identifier(_) => write!(f, "Identifier"),
             TokenType::String(_) => write!(f, "String"),
+            TokenType::Interpolation(_) => write!(f, "Interpolation"),
             TokenType::Number(_) => write!(f, "Number"),
+            TokenType::Int(_) => write!(f, "Int"),
+            TokenType::BigInt(_) => write!(f, "BigInt"),
             TokenType::And => write!(f, "And"),
             TokenType::Class => write!(f, "Class"),
             TokenType::Else => write!(f, "Else"),
@@ -344,6 +553,14 @@ impl Display for TokenType {
             TokenType::For => write!(f, "For"),
             TokenType::Fn => write!(f, "Fn"),
             TokenType::If => write!(f, "If"),
+            TokenType::Match => write!(f, "Match"),
+            TokenType::Catch => write!(f, "Catch"),
+            TokenType::Export => write!(f, "Export"),
+            TokenType::From => write!(f, "From"),
+            TokenType::Import => write!(f, "Import"),
+            TokenType::Finally => write!(f, "Finally"),
+            TokenType::Throw => write!(f, "Throw"),
+            TokenType::Try => write!(f, "Try"),
             TokenType::Nil => write!(f, "Nil"),
             TokenType::Or => write!(f, "Or"),
             TokenType::Print => write!(f, "Print"),
@@ -353,6 +570,7 @@ impl Display for TokenType {
             TokenType::True => write!(f, "True"),
             TokenType::Var => write!(f, "Var"),
             TokenType::While => write!(f, "While"),
+            TokenType::Newline => write!(f, "Newline"),
             TokenType::Error(_) => write!(f, "Error"),
         }
     }
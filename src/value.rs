@@ -1,18 +1,39 @@
+use crate::bigint::BigInt;
 use crate::function::Function;
+use crate::stdlib::fs::FileHandle;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
 use std::fmt::{self, Display, Formatter};
+use std::rc::Rc;
 
 #[derive(Debug, Clone)]
 pub enum Value {
     Bool(bool),
     Nil,
     Number(f64),
+    Int(i64),
+    BigInt(BigInt),
     String(String),
-    Function(Function),
+    Function(Rc<Function>),
+    List(Rc<RefCell<Vec<Value>>>),
+    Map(Rc<RefCell<BTreeMap<String, Value>>>), // keys are kept sorted
+    Result(Rc<Result<Value, Value>>),          // made by `Ok(v)` and `Err(e)`
+    File(Rc<RefCell<FileHandle>>),
 }
 
 impl From<i64> for Value {
     fn from(n: i64) -> Self {
-        Value::Number(n as f64)
+        Value::Int(n)
+    }
+}
+
+/// integers that fit in an i64 are always stored as `Value::Int`
+impl From<BigInt> for Value {
+    fn from(n: BigInt) -> Self {
+        match n.to_i64() {
+            Some(x) => Value::Int(x),
+            None => Value::BigInt(n),
+        }
     }
 }
 
@@ -21,19 +42,56 @@ impl Display for Value {
         match self {
             Value::Bool(x) => write!(f, "{}", x),
             Value::Number(x) => write!(f, "{}", x),
+            Value::Int(x) => write!(f, "{}", x),
+            Value::BigInt(x) => write!(f, "{}", x),
             Value::Nil => write!(f, "nil"),
             Value::String(s) => write!(f, "{}", s),
             Value::Function(func) => {
                 if func.native {
                     write!(f, "<native fn>")
+                } else if func.name.is_empty() {
+                    write!(f, "<fn>")
                 } else {
                     write!(f, "<fn {}>", func.name)
                 }
             }
+            Value::List(list) => {
+                let items: Vec<String> = list.borrow().iter().map(|v| v.repr()).collect();
+                write!(f, "[{}]", items.join(", "))
+            }
+            Value::Map(map) => {
+                let map = map.borrow();
+                if map.is_empty() {
+                    return write!(f, "[:]");
+                }
+
+                let entries: Vec<String> = map
+                    .iter()
+                    .map(|(k, v)| format!("'{}': {}", k, v.repr()))
+                    .collect();
+                write!(f, "[{}]", entries.join(", "))
+            }
+            Value::Result(result) => match result.as_ref() {
+                Ok(value) => write!(f, "Ok({})", value.repr()),
+                Err(error) => write!(f, "Err({})", error.repr()),
+            },
+            Value::File(file) => write!(f, "<file {}>", file.borrow().path),
         }
     }
 }
 
+impl From<Vec<Value>> for Value {
+    fn from(items: Vec<Value>) -> Self {
+        Value::List(Rc::new(RefCell::new(items)))
+    }
+}
+
+impl From<BTreeMap<String, Value>> for Value {
+    fn from(entries: BTreeMap<String, Value>) -> Self {
+        Value::Map(Rc::new(RefCell::new(entries)))
+    }
+}
+
 impl Value {
     pub fn is_falsey(&self) -> bool {
         match self {
@@ -43,7 +101,42 @@ impl Value {
         }
     }
 
+    /// formats a value as it's written in source, quoting strings
+    pub fn repr(&self) -> String {
+        match self {
+            Value::String(s) => format!("'{}'", s),
+            value => value.to_string(),
+        }
+    }
+
+    /// name of the value's type, used in error messages
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Bool(_) => "bool",
+            Value::Nil => "nil",
+            Value::Number(_) => "float",
+            Value::Int(_) | Value::BigInt(_) => "int",
+            Value::String(_) => "string",
+            Value::Function(_) => "function",
+            Value::List(_) => "list",
+            Value::Map(_) => "map",
+            Value::Result(_) => "result",
+            Value::File(_) => "file",
+        }
+    }
+
     pub fn eq(&self, other: &Value) -> bool {
+        // ints and floats compare by numeric value
+        match (self, other) {
+            (Value::Int(a), Value::Number(b)) | (Value::Number(b), Value::Int(a)) => {
+                return int_eq_float(*a, *b)
+            }
+            (Value::BigInt(a), Value::Number(b)) | (Value::Number(b), Value::BigInt(a)) => {
+                return *b == b.trunc() && BigInt::from_f64(*b).is_some_and(|b| *a == b)
+            }
+            _ => (),
+        }
+
         if std::mem::discriminant(self) != std::mem::discriminant(other) {
             return false;
         }
@@ -51,16 +144,45 @@ impl Value {
         match (self, other) {
             (Value::Bool(a), Value::Bool(b)) => a == b,
             (Value::Number(a), Value::Number(b)) => a == b,
+            (Value::Int(a), Value::Int(b)) => a == b,
+            (Value::BigInt(a), Value::BigInt(b)) => a == b,
             (Value::String(a), Value::String(b)) => a == b,
             (Value::Nil, _) => true,
+            (Value::Function(a), Value::Function(b)) => Rc::ptr_eq(a, b),
+            (Value::List(a), Value::List(b)) if Rc::ptr_eq(a, b) => true,
+            (Value::Map(a), Value::Map(b)) if Rc::ptr_eq(a, b) => true,
+            (Value::File(a), Value::File(b)) => Rc::ptr_eq(a, b),
+            (Value::List(a), Value::List(b)) => {
+                let (a, b) = (a.borrow(), b.borrow());
+                a.len() == b.len() && a.iter().zip(b.iter()).all(|(x, y)| x.eq(y))
+            }
+            (Value::Map(a), Value::Map(b)) => {
+                let (a, b) = (a.borrow(), b.borrow());
+                a.len() == b.len()
+                    && a.iter()
+                        .zip(b.iter())
+                        .all(|((k1, v1), (k2, v2))| k1 == k2 && v1.eq(v2))
+            }
+            (Value::Result(a), Value::Result(b)) => match (a.as_ref(), b.as_ref()) {
+                (Ok(a), Ok(b)) | (Err(a), Err(b)) => a.eq(b),
+                _ => false,
+            },
             _ => unreachable!("Unrecognized value equality comparison"),
         }
     }
 }
 
+/// compares exactly, rather than rounding the int to the nearest float
+fn int_eq_float(a: i64, b: f64) -> bool {
+    // 2^63 is exact as a float, so this excludes everything outside i64
+    b == b.trunc() && (-9.223372036854776e18..9.223372036854776e18).contains(&b) && b as i64 == a
+}
+
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison, clippy::approx_constant)]
 mod tests {
     use super::*;
+    use crate::function::FunctionType;
 
     #[test]
     fn true_is_not_falsey() {
@@ -82,6 +204,11 @@ mod tests {
         assert_eq!(Value::Number(3.14).is_falsey(), false);
     }
 
+    #[test]
+    fn zero_is_not_falsey() {
+        assert!(!Value::Int(0).is_falsey());
+    }
+
     #[test]
     fn nil_equals_nil() {
         let a = Value::Nil;
@@ -103,6 +230,50 @@ mod tests {
         assert_eq!(a.eq(&b), false);
     }
 
+    #[test]
+    fn equal_ints_are_equal() {
+        let a = Value::Int(1 << 60);
+        let b = Value::Int(1 << 60);
+        assert!(a.eq(&b));
+    }
+
+    #[test]
+    fn ints_equal_floats_with_same_value() {
+        let a = Value::Int(3);
+        let b = Value::Number(3.0);
+        assert!(a.eq(&b));
+        assert!(b.eq(&a));
+    }
+
+    #[test]
+    fn ints_and_floats_compare_exactly() {
+        let a = Value::Int(9007199254740993);
+        let b = Value::Number(9007199254740992.0);
+        assert!(!a.eq(&b));
+        assert!(!b.eq(&a));
+        assert!(!Value::Int(i64::MAX).eq(&Value::Number(9.223372036854776e18)));
+        assert!(!Value::Int(3).eq(&Value::Number(3.5)));
+
+        let big = BigInt::from_str_radix("100000000000000000001", 10).unwrap();
+        assert!(!Value::from(big).eq(&Value::Number(1e20)));
+        let big = BigInt::from_str_radix("100000000000000000000", 10).unwrap();
+        assert!(Value::Number(1e20).eq(&Value::from(big)));
+    }
+
+    #[test]
+    fn bigints_that_fit_become_ints() {
+        let n = BigInt::from_str_radix("-42", 10).unwrap();
+        assert!(matches!(Value::from(n), Value::Int(-42)));
+    }
+
+    #[test]
+    fn functions_compare_by_identity() {
+        let f = Value::Function(Rc::new(Function::new(String::from("f"), FunctionType::Fn)));
+        let g = Value::Function(Rc::new(Function::new(String::from("f"), FunctionType::Fn)));
+        assert!(f.eq(&f.clone()));
+        assert!(!f.eq(&g));
+    }
+
     #[test]
     fn different_types_are_not_equal() {
         let a = Value::Number(0.0);
@@ -123,4 +294,32 @@ mod tests {
         let b = Value::String(String::from("topaz is neat!"));
         assert_eq!(a.eq(&b), true);
     }
+
+    #[test]
+    fn lists_compare_by_contents() {
+        let a = Value::from(vec![Value::Int(1), Value::from(vec![Value::Nil])]);
+        let b = Value::from(vec![Value::Number(1.0), Value::from(vec![Value::Nil])]);
+        assert!(a.eq(&b));
+        assert!(!a.eq(&Value::from(vec![Value::Int(1)])));
+    }
+
+    #[test]
+    fn collections_quote_nested_strings() {
+        let mut map = BTreeMap::new();
+        map.insert(
+            String::from("k"),
+            Value::from(vec![Value::String(String::from("v"))]),
+        );
+        assert_eq!(Value::from(map).to_string(), "['k': ['v']]");
+        assert_eq!(Value::from(BTreeMap::new()).to_string(), "[:]");
+    }
+
+    #[test]
+    fn results_compare_by_variant_and_value() {
+        let ok = Value::Result(Rc::new(Ok(Value::Int(1))));
+        let err = Value::Result(Rc::new(Err(Value::Int(1))));
+        assert!(ok.eq(&Value::Result(Rc::new(Ok(Value::Number(1.0))))));
+        assert!(!ok.eq(&err));
+        assert_eq!(err.to_string(), "Err(1)");
+    }
 }
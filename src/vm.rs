@@ -1,14 +1,34 @@
-use crate::function::Function;
+use crate::bigint::BigInt;
+use crate::function::{Function, FunctionType, NativeFn, Upvalue};
+use crate::method;
+use crate::module::{self, Module};
+use crate::native;
 use crate::opcode::Opcode;
 use crate::operator::Operator;
+use crate::parser::Parser;
+use crate::stdlib::{self, random::Rng};
 use crate::value::Value;
 
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 
 pub struct Vm {
     stack: Vec<Value>,
-    globals: HashMap<String, Value>,
+    builtins: HashMap<String, Value>, // natives, visible from every module
+    main: Rc<Module>,
+    native_modules: HashMap<String, Value>,
+    modules: HashMap<PathBuf, Value>, // exports of each module loaded so far
+    loading: Vec<PathBuf>,            // modules whose code is running, to catch cycles
+    search_path: Vec<PathBuf>,
+    packages: HashMap<String, PathBuf>, // entry module of each dependency
     frames: Vec<CallFrame>,
+    open_upvalues: Vec<Rc<RefCell<Upvalue>>>, // captured variables still on the stack
+    thrown: Option<(Value, usize)>,           // value being thrown and the line it was thrown on
+    rng: Rng,                                 // shared by the random module
 }
 
 pub enum InterpretError {
@@ -17,35 +37,116 @@ pub enum InterpretError {
 }
 
 struct CallFrame {
-    function: Function,
-    ip: usize,   // ip of caller to return to
-    base: usize, // index of base of stack
+    function: Rc<Function>,
+    ip: usize,                    // ip of caller to return to
+    base: usize,                  // stack index of the frame's slot 0, the function being called
+    handlers: Vec<Handler>,       // enclosing `try` blocks, innermost last
+    exports: Option<Vec<String>>, // set when running an imported module, which returns these globals
 }
 
 impl CallFrame {
-    pub fn new(function: Function, base: usize) -> CallFrame {
+    pub fn new(function: Rc<Function>, base: usize) -> CallFrame {
         CallFrame {
             function,
             ip: 0,
             base,
+            handlers: Vec::new(),
+            exports: None,
         }
     }
 }
 
+/// where a `try` block's catch starts and how much of the stack it keeps
+struct Handler {
+    catch_ip: usize,
+    finally_ip: Option<usize>, // run on the way out when the function returns
+    stack_len: usize,
+}
+
+/// largest shift amount accepted by `<<`, keeps `1 << n` from exhausting memory
+const MAX_SHIFT: i64 = 1 << 24;
+
 impl Vm {
     pub fn new() -> Vm {
-        Vm {
+        let mut vm = Vm {
             stack: Vec::new(),
-            globals: HashMap::new(),
+            builtins: HashMap::new(),
+            main: Rc::new(Module::new("", None)),
+            native_modules: HashMap::new(),
+            modules: HashMap::new(),
+            loading: Vec::new(),
+            search_path: Vec::new(),
+            packages: HashMap::new(),
             frames: Vec::new(),
-        }
+            open_upvalues: Vec::new(),
+            thrown: None,
+            rng: Rng::from_time(),
+        };
+
+        native::define_natives(&mut vm);
+        stdlib::define_modules(&mut vm);
+        vm
+    }
+
+    pub fn define_native(&mut self, name: &str, native_fn: NativeFn) {
+        let f = Function::native(name, native_fn);
+        self.builtins
+            .insert(String::from(name), Value::Function(Rc::new(f)));
+    }
+
+    /// makes a module written in rust importable by name
+    pub fn define_module(&mut self, name: &str, module: Value) {
+        self.native_modules.insert(String::from(name), module);
+    }
+
+    /// makes the random module give the same numbers on every run
+    pub fn seed_random(&mut self, seed: u64) {
+        self.rng = Rng::new(seed);
+    }
+
+    pub fn rng(&mut self) -> &mut Rng {
+        &mut self.rng
+    }
+
+    /// sets the file of the main module, which its imports are relative to
+    pub fn set_script_path(&mut self, path: &Path) {
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        self.main = Rc::new(Module::new("", Some(path.clone())));
+        self.loading = vec![path];
+    }
+
+    /// adds a directory to look in for modules not found next to the importer
+    pub fn add_search_path(&mut self, dir: PathBuf) {
+        self.search_path.push(dir);
+    }
+
+    /// lets `import name` load a dependency's entry module
+    pub fn add_package(&mut self, name: &str, entry: PathBuf) {
+        self.packages.insert(String::from(name), entry);
     }
 
-    pub fn run(&mut self, function: Function) -> Result<Value, InterpretError> {
+    pub fn run(&mut self, mut function: Function) -> Result<Value, InterpretError> {
+        function.module = Some(self.main.clone());
+
         // push "stack frame" of top level script onto stack
-        let cf = CallFrame::new(function, 0);
+        let function = Rc::new(function);
+        self.push(Value::Function(function.clone()));
+        let cf = CallFrame::new(function, self.stack.len() - 1);
         self.frames.push(cf);
 
+        loop {
+            match self.execute() {
+                Err(InterpretError::RuntimeError) => {
+                    if !self.catch_thrown() {
+                        return Err(InterpretError::RuntimeError);
+                    }
+                }
+                result => return result,
+            }
+        }
+    }
+
+    fn execute(&mut self) -> Result<Value, InterpretError> {
         loop {
             // debug information
             if cfg!(debug_assertions) {
@@ -62,19 +163,9 @@ impl Vm {
             match Opcode::from(instruction) {
                 Opcode::Return => {
                     let result = self.pop();
-                    let frame = self.frames.pop().unwrap();
-
-                    if self.frames.is_empty() {
+                    if let Some(result) = self.return_value(result) {
                         return Ok(result);
                     }
-
-                    // return caller's stack to how it was before function call
-                    let diff = self.stack.len() - frame.base + 1;
-                    for _ in 0..diff {
-                        self.pop();
-                    }
-
-                    self.push(result);
                 }
                 Opcode::Constant => {
                     let constant = self.read_constant();
@@ -84,15 +175,20 @@ impl Vm {
                     let value = self.pop();
                     let negated_value = match value {
                         Value::Number(num) => Value::Number(-num),
+                        Value::Int(num) => match num.checked_neg() {
+                            Some(n) => Value::Int(n),
+                            None => Value::from(-&BigInt::from(num)),
+                        },
+                        Value::BigInt(num) => Value::from(-&num),
                         _ => return Err(self.runtime_error("Operand must be a number")),
                     };
                     self.push(negated_value)
                 }
-                Opcode::Add => self.binary_op(Operator::Plus),
-                Opcode::Subtract => self.binary_op(Operator::Minus),
-                Opcode::Multiply => self.binary_op(Operator::Star),
-                Opcode::Divide => self.binary_op(Operator::Slash),
-                Opcode::Mod => self.binary_op(Operator::Mod),
+                Opcode::Add => self.binary_op(Operator::Plus)?,
+                Opcode::Subtract => self.binary_op(Operator::Minus)?,
+                Opcode::Multiply => self.binary_op(Operator::Star)?,
+                Opcode::Divide => self.binary_op(Operator::Slash)?,
+                Opcode::Mod => self.binary_op(Operator::Mod)?,
                 Opcode::Nil => self.push(Value::Nil),
                 Opcode::True => self.push(Value::Bool(true)),
                 Opcode::False => self.push(Value::Bool(false)),
@@ -105,14 +201,26 @@ impl Vm {
                     let a = self.pop();
                     self.push(Value::Bool(a.eq(&b)));
                 }
-                Opcode::Greater => self.binary_op(Operator::GreaterThan),
-                Opcode::Less => self.binary_op(Operator::LessThan),
-                Opcode::LogicalAnd => self.binary_op(Operator::AmpAmp),
-                Opcode::LogicalOr => self.binary_op(Operator::PipePipe),
-                Opcode::BitwiseAnd => self.binary_op(Operator::Amp),
-                Opcode::BitwiseOr => self.binary_op(Operator::Pipe),
+                Opcode::Greater => self.binary_op(Operator::GreaterThan)?,
+                Opcode::Less => self.binary_op(Operator::LessThan)?,
+                Opcode::LogicalAnd => self.binary_op(Operator::AmpAmp)?,
+                Opcode::LogicalOr => self.binary_op(Operator::PipePipe)?,
+                Opcode::BitwiseAnd => self.binary_op(Operator::Amp)?,
+                Opcode::BitwiseOr => self.binary_op(Operator::Pipe)?,
+                Opcode::BitwiseXor => self.binary_op(Operator::Caret)?,
+                Opcode::IntDivide => self.binary_op(Operator::SlashSlash)?,
+                Opcode::ShiftLeft => self.binary_op(Operator::LessLess)?,
+                Opcode::ShiftRight => self.binary_op(Operator::GreaterGreater)?,
+                Opcode::Power => self.binary_op(Operator::StarStar)?,
+                Opcode::Concat => {
+                    // stringify the top values on the stack and join them in order
+                    let count = self.read_byte() as usize;
+                    let start = self.stack.len() - count;
+                    let result = self.stack.drain(start..).map(|v| v.to_string()).collect();
+                    self.push(Value::String(result));
+                }
                 Opcode::Print => {
-                    println!("{}", self.peek(0));
+                    println!("{}", self.pop());
                 }
                 Opcode::Pop => {
                     self.pop();
@@ -120,13 +228,12 @@ impl Vm {
                 Opcode::GetGlobal => {
                     let constant = self.read_constant();
                     if let Value::String(name) = constant {
-                        match self.globals.get(&name) {
-                            Some(val) => self.push(val.clone()),
+                        let value = self.module().globals.borrow().get(&name).cloned();
+                        match value.or_else(|| self.builtins.get(&name).cloned()) {
+                            Some(val) => self.push(val),
                             None => {
-                                self.runtime_error(
-                                    format!("Undefined variable {}", &name).as_str(),
-                                );
-                                return Err(InterpretError::RuntimeError);
+                                let msg = format!("Undefined variable {}", &name);
+                                return Err(self.runtime_error(&msg));
                             }
                         }
                     } else {
@@ -137,11 +244,10 @@ impl Vm {
                     let constant = self.read_constant();
                     let (name, value) = match constant {
                         Value::String(name) => (name, self.peek(0).clone()),
-                        Value::Function(f) => (f.name.clone(), Value::Function(f)),
                         _ => unreachable!("Unknown value in SetGlobal"),
                     };
 
-                    self.globals.insert(name, value);
+                    self.module().globals.borrow_mut().insert(name, value);
                 }
                 Opcode::GetLocal => {
                     let base = self.frames.last_mut().unwrap().base;
@@ -153,6 +259,222 @@ impl Vm {
                     let slot = self.read_byte() as usize;
                     self.stack[base + slot] = self.peek(0).clone();
                 }
+                Opcode::GetUpvalue => {
+                    let slot = self.read_byte() as usize;
+                    let upvalue = self.frames.last().unwrap().function.upvalues[slot].clone();
+                    let value = match &*upvalue.borrow() {
+                        Upvalue::Open(index) => self.stack[*index].clone(),
+                        Upvalue::Closed(value) => value.clone(),
+                    };
+                    self.push(value);
+                }
+                Opcode::SetUpvalue => {
+                    let slot = self.read_byte() as usize;
+                    let upvalue = self.frames.last().unwrap().function.upvalues[slot].clone();
+                    let value = self.peek(0).clone();
+                    let mut upvalue = upvalue.borrow_mut();
+                    match &mut *upvalue {
+                        Upvalue::Open(index) => self.stack[*index] = value,
+                        Upvalue::Closed(closed) => *closed = value,
+                    }
+                }
+                Opcode::Closure => {
+                    let mut f = match self.read_constant() {
+                        Value::Function(f) => (*f).clone(),
+                        _ => unreachable!("Did not receive a Function in Closure"),
+                    };
+
+                    f.module = Some(self.module().clone());
+
+                    let base = self.frames.last().unwrap().base;
+                    for _ in 0..f.upvalue_count {
+                        let is_local = self.read_byte() == 1;
+                        let index = self.read_byte() as usize;
+                        let upvalue = if is_local {
+                            self.capture_upvalue(base + index)
+                        } else {
+                            self.frames.last().unwrap().function.upvalues[index].clone()
+                        };
+                        f.upvalues.push(upvalue);
+                    }
+
+                    self.push(Value::Function(Rc::new(f)));
+                }
+                Opcode::PopLocals => {
+                    // drop a block's locals from beneath the block's value
+                    let count = self.read_byte() as usize;
+                    let value = self.pop();
+                    let start = self.stack.len() - count;
+                    self.close_upvalues(start);
+                    self.stack.truncate(start);
+                    self.push(value);
+                }
+                Opcode::BuildList => {
+                    let count = self.read_byte() as usize;
+                    let start = self.stack.len() - count;
+                    let items: Vec<Value> = self.stack.drain(start..).collect();
+                    self.push(Value::from(items));
+                }
+                Opcode::BuildMap => {
+                    let count = self.read_byte() as usize;
+                    let start = self.stack.len() - 2 * count;
+                    let values: Vec<Value> = self.stack.drain(start..).collect();
+
+                    let mut map = BTreeMap::new();
+                    for pair in values.chunks(2) {
+                        match &pair[0] {
+                            Value::String(key) => map.insert(key.clone(), pair[1].clone()),
+                            key => {
+                                let msg =
+                                    format!("Map keys must be strings, not {}", key.type_name());
+                                return Err(self.runtime_error(&msg));
+                            }
+                        };
+                    }
+                    self.push(Value::from(map));
+                }
+                Opcode::GetIndex => {
+                    let index = self.pop();
+                    let target = self.pop();
+                    let value = self.get_index(&target, &index)?;
+                    self.push(value);
+                }
+                Opcode::SetIndex => {
+                    let value = self.pop();
+                    let index = self.pop();
+                    let target = self.pop();
+                    self.set_index(&target, &index, value.clone())?;
+                    self.push(value);
+                }
+                Opcode::IsList => {
+                    let len = self.read_byte() as usize;
+                    let matched = match self.pop() {
+                        Value::List(list) => list.borrow().len() == len,
+                        _ => false,
+                    };
+                    self.push(Value::Bool(matched));
+                }
+                Opcode::IsMap => {
+                    let matched = matches!(self.pop(), Value::Map(_));
+                    self.push(Value::Bool(matched));
+                }
+                Opcode::HasKey => {
+                    let key = self.read_constant();
+                    let matched = match (self.pop(), key) {
+                        (Value::Map(map), Value::String(key)) => map.borrow().contains_key(&key),
+                        _ => false,
+                    };
+                    self.push(Value::Bool(matched));
+                }
+                Opcode::InRange => {
+                    let inclusive = self.read_byte() == 1;
+                    let high = self.pop();
+                    let low = self.pop();
+                    let value = self.pop();
+
+                    let above = matches!(
+                        compare(&value, &low),
+                        Some(Ordering::Greater | Ordering::Equal)
+                    );
+                    let below = match compare(&value, &high) {
+                        Some(Ordering::Less) => true,
+                        Some(Ordering::Equal) => inclusive,
+                        _ => false,
+                    };
+                    self.push(Value::Bool(above && below));
+                }
+                Opcode::JumpTable => {
+                    // jump to the entry for the int on top of the stack, or the
+                    // default entry after the table
+                    let min = match self.read_constant() {
+                        Value::Int(min) => min,
+                        _ => unreachable!("Jump table without an int minimum"),
+                    };
+                    let count = self.read_byte() as usize;
+
+                    // a float equal to an int takes the int's entry, as it would
+                    // with `==`. Bigints are never in the range of an i64 table
+                    let n = match self.peek(0) {
+                        Value::Int(n) => Some(*n as i128),
+                        Value::Number(x) if x.fract() == 0f64 && x.abs() < 1e19 => Some(*x as i128),
+                        _ => None,
+                    };
+                    let entry = match n {
+                        Some(n) if n >= min as i128 && n - (min as i128) < count as i128 => {
+                            (n - min as i128) as usize
+                        }
+                        _ => count,
+                    };
+
+                    let frame = self.frames.last_mut().unwrap();
+                    let table_end = frame.ip + 2 * (count + 1);
+                    frame.ip += 2 * entry;
+                    let offset = self.read_short() as usize;
+                    self.frames.last_mut().unwrap().ip = table_end + offset;
+                }
+                Opcode::MatchError => {
+                    let value = self.pop();
+                    let msg = format!("No match arm for {}", value.repr());
+                    return Err(self.runtime_error(&msg));
+                }
+                Opcode::ExpectList => {
+                    let len = self.read_byte() as usize;
+                    let msg = match self.peek(0) {
+                        Value::List(list) if list.borrow().len() == len => None,
+                        Value::List(list) => Some(format!(
+                            "Expected {} values to destructure but got {}",
+                            len,
+                            list.borrow().len()
+                        )),
+                        value => Some(format!(
+                            "Cannot destructure {} as a list",
+                            value.type_name()
+                        )),
+                    };
+
+                    if let Some(msg) = msg {
+                        return Err(self.runtime_error(&msg));
+                    }
+                }
+                Opcode::ExpectKey => {
+                    let key = match self.read_constant() {
+                        Value::String(key) => key,
+                        _ => unreachable!("Did not receive a String in ExpectKey"),
+                    };
+                    let msg = match self.peek(0) {
+                        Value::Map(map) if map.borrow().contains_key(&key) => None,
+                        Value::Map(_) => Some(format!("Missing key '{}' to destructure", key)),
+                        value => Some(format!("Cannot destructure {} as a map", value.type_name())),
+                    };
+
+                    if let Some(msg) = msg {
+                        return Err(self.runtime_error(&msg));
+                    }
+                }
+                Opcode::Duplicate => {
+                    let count = self.read_byte() as usize;
+                    let start = self.stack.len() - count;
+                    self.stack.extend_from_within(start..);
+                }
+                Opcode::GetField => {
+                    let name = self.read_string();
+                    let target = self.pop();
+                    let value = self.get_field(&target, &name)?;
+                    self.push(value);
+                }
+                Opcode::SetField => {
+                    let name = self.read_string();
+                    let value = self.pop();
+                    match self.pop() {
+                        Value::Map(map) => map.borrow_mut().insert(name, value.clone()),
+                        target => {
+                            let msg =
+                                format!("Cannot set field '{}' on {}", name, target.type_name());
+                            return Err(self.runtime_error(&msg));
+                        }
+                    };
+                    self.push(value);
+                }
                 Opcode::JumpIfFalse => {
                     let offset = self.read_short() as usize;
                     if self.peek(0).is_falsey() {
@@ -169,29 +491,432 @@ impl Vm {
                 }
                 Opcode::Call => {
                     let num_args = self.read_byte() as usize;
-                    let function = self.peek(num_args);
-                    let f = match function {
-                        Value::Function(f) => f,
-                        _ => {
-                            return Err(InterpretError::RuntimeError);
+                    self.call(num_args)?;
+                }
+                Opcode::Invoke => {
+                    let name = self.read_string();
+                    let num_args = self.read_byte() as usize;
+                    let receiver = self.peek(num_args).clone();
+                    let receiver_slot = self.stack.len() - num_args - 1;
+
+                    if let Value::Map(_) = receiver {
+                        // call the function stored in the field
+                        self.stack[receiver_slot] = self.get_field(&receiver, &name)?;
+                        self.call(num_args)?;
+                    } else {
+                        let args = self.stack[receiver_slot + 1..].to_vec();
+                        let result = method::call_method(self, &receiver, &name, &args);
+                        self.stack.truncate(receiver_slot);
+                        match result {
+                            Ok(value) => self.push(value),
+                            Err(msg) => return Err(self.runtime_error(&msg)),
+                        }
+                    }
+                }
+                Opcode::IsErr => {
+                    let is_err = match self.peek(0) {
+                        Value::Result(result) => result.is_err(),
+                        value => {
+                            let msg = format!(
+                                "Expected a result before '?' but got {}",
+                                value.type_name()
+                            );
+                            return Err(self.runtime_error(&msg));
                         }
                     };
-
-                    let cf = CallFrame::new(f.clone(), self.stack.len() - num_args);
-                    self.frames.push(cf);
+                    self.push(Value::Bool(is_err));
+                }
+                Opcode::UnwrapOk => match self.pop() {
+                    Value::Result(result) => match result.as_ref() {
+                        Ok(value) => self.push(value.clone()),
+                        Err(_) => {
+                            let msg = format!("Uncaught {}", Value::Result(result).repr());
+                            return Err(self.runtime_error(&msg));
+                        }
+                    },
+                    value => {
+                        let msg =
+                            format!("Expected a result before '?' but got {}", value.type_name());
+                        return Err(self.runtime_error(&msg));
+                    }
+                },
+                Opcode::PushHandler => {
+                    // each jump is relative to the end of its own operand
+                    let offset = self.read_short() as usize;
+                    let catch_ip = self.frames.last().unwrap().ip + offset;
+                    let finally_ip = match self.read_short() as usize {
+                        0 => None,
+                        offset => Some(self.frames.last().unwrap().ip + offset),
+                    };
+                    let stack_len = self.stack.len();
+                    self.frames.last_mut().unwrap().handlers.push(Handler {
+                        catch_ip,
+                        finally_ip,
+                        stack_len,
+                    });
+                }
+                Opcode::PopHandler => {
+                    self.frames.last_mut().unwrap().handlers.pop();
+                }
+                Opcode::Throw => {
+                    let value = self.pop();
+                    return Err(self.throw(value));
+                }
+                Opcode::Import => {
+                    let spec = self.read_string();
+                    self.import(&spec)?;
+                }
+                Opcode::EndFinally => {
+                    // the flag beneath the finally block says how the try ended
+                    match self.pop() {
+                        Value::Bool(true) => {
+                            let error = self.pop();
+                            return Err(self.throw(error));
+                        }
+                        Value::Nil => {
+                            let result = self.pop();
+                            if let Some(result) = self.return_value(result) {
+                                return Ok(result);
+                            }
+                        }
+                        _ => (),
+                    }
                 }
                 _ => return Err(InterpretError::CompileError),
             };
         }
     }
 
+    /// returns from the current frame, running the finally blocks still open in
+    /// it first. Gives back the result once the outermost frame has returned
+    fn return_value(&mut self, result: Value) -> Option<Value> {
+        while let Some(handler) = self.frames.last_mut().unwrap().handlers.pop() {
+            if let Some(finally_ip) = handler.finally_ip {
+                self.frames.last_mut().unwrap().ip = finally_ip;
+                self.close_upvalues(handler.stack_len);
+                self.stack.truncate(handler.stack_len);
+                self.push(result);
+                return None;
+            }
+        }
+
+        let frame = self.frames.pop().unwrap();
+
+        // return caller's stack to how it was before function call
+        self.close_upvalues(frame.base);
+        self.stack.truncate(frame.base);
+
+        if self.frames.is_empty() {
+            return Some(result);
+        }
+
+        if let Some(names) = &frame.exports {
+            let module = frame.function.module.as_ref().unwrap();
+            let exports = module.exports(names);
+            let path = self.loading.pop().unwrap();
+            self.modules.insert(path, exports.clone());
+            self.push(exports);
+        } else {
+            self.push(result);
+        }
+        None
+    }
+
+    /// module of the running code
+    fn module(&self) -> &Rc<Module> {
+        self.frames
+            .last()
+            .unwrap()
+            .function
+            .module
+            .as_ref()
+            .unwrap()
+    }
+
+    /// pushes a module's exports, first running its code in a new frame if
+    /// it hasn't been loaded yet
+    fn import(&mut self, spec: &str) -> Result<(), InterpretError> {
+        if let Some(module) = self.native_modules.get(spec) {
+            self.push(module.clone());
+            return Ok(());
+        }
+
+        let dir = self.module().dir();
+        let path = match module::resolve(spec, &dir, &self.search_path, &self.packages) {
+            Some(path) => path,
+            None => return Err(self.runtime_error(&format!("Cannot find module '{}'", spec))),
+        };
+
+        if let Some(exports) = self.modules.get(&path) {
+            self.push(exports.clone());
+            return Ok(());
+        }
+
+        if let Some(start) = self.loading.iter().position(|p| *p == path) {
+            let cycle: Vec<String> = self.loading[start..]
+                .iter()
+                .chain(std::iter::once(&path))
+                .map(|p| {
+                    p.file_name()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                        .into_owned()
+                })
+                .collect();
+            let msg = format!("Import cycle: {}", cycle.join(" -> "));
+            return Err(self.runtime_error(&msg));
+        }
+
+        let source = match fs::read_to_string(&path) {
+            Ok(source) => source,
+            Err(_) => return Err(self.runtime_error(&format!("Cannot read module '{}'", spec))),
+        };
+        let (mut function, exports) = match Parser::new(source).compile_module() {
+            Ok(compiled) => compiled,
+            Err(_) => return Err(self.runtime_error(&format!("Cannot compile module '{}'", spec))),
+        };
+
+        let name = module::module_name(spec);
+        function.name = String::from(name);
+        function.module = Some(Rc::new(Module::new(name, Some(path.clone()))));
+        self.loading.push(path);
+
+        let function = Rc::new(function);
+        self.push(Value::Function(function.clone()));
+        let mut frame = CallFrame::new(function, self.stack.len() - 1);
+        frame.exports = Some(exports);
+        self.frames.push(frame);
+        Ok(())
+    }
+
+    /// calls the function beneath the arguments, natives run to completion
+    fn call(&mut self, num_args: usize) -> Result<(), InterpretError> {
+        let function = self.peek(num_args);
+        let f = match function {
+            Value::Function(f) => f,
+            value => {
+                let msg = format!("Can only call functions, not {}", value.type_name());
+                return Err(self.runtime_error(&msg));
+            }
+        };
+
+        if let Some(native_fn) = f.native_fn {
+            let args_start = self.stack.len() - num_args;
+            let args = self.stack[args_start..].to_vec();
+            let result = native_fn(self, &args);
+
+            // pop arguments and the native function itself
+            self.stack.truncate(args_start - 1);
+            match result {
+                Ok(value) => self.push(value),
+                Err(msg) => return Err(self.runtime_error(&msg)),
+            }
+        } else if f.num_params != num_args {
+            let msg = format!("Expected {} arguments but got {}", f.num_params, num_args);
+            return Err(self.runtime_error(&msg));
+        } else {
+            let cf = CallFrame::new(f.clone(), self.stack.len() - num_args - 1);
+            self.frames.push(cf);
+        }
+
+        Ok(())
+    }
+
+    /// fields of a map are its string keys
+    fn get_field(&mut self, target: &Value, name: &str) -> Result<Value, InterpretError> {
+        let value = match target {
+            Value::Map(map) => map.borrow().get(name).cloned(),
+            _ => None,
+        };
+
+        match value {
+            Some(value) => Ok(value),
+            None => {
+                let msg = format!("Undefined field '{}' on {}", name, target.type_name());
+                Err(self.runtime_error(&msg))
+            }
+        }
+    }
+
+    /// subscripts a list or string with an int, counting back from the end
+    /// when negative, or a map with a string key
+    fn get_index(&mut self, target: &Value, index: &Value) -> Result<Value, InterpretError> {
+        match (target, index) {
+            (Value::List(list), Value::Int(i)) => {
+                let len = list.borrow().len();
+                let i = self.list_index(*i, len)?;
+                Ok(list.borrow()[i].clone())
+            }
+            (Value::Map(map), Value::String(key)) => {
+                Ok(map.borrow().get(key).cloned().unwrap_or(Value::Nil))
+            }
+            (Value::String(s), Value::Int(i)) => {
+                let len = s.chars().count();
+                let i = self.list_index(*i, len)?;
+                Ok(Value::String(s.chars().nth(i).unwrap().to_string()))
+            }
+            (target, index) => {
+                let msg = format!(
+                    "Cannot index {} with {}",
+                    target.type_name(),
+                    index.type_name()
+                );
+                Err(self.runtime_error(&msg))
+            }
+        }
+    }
+
+    fn set_index(
+        &mut self,
+        target: &Value,
+        index: &Value,
+        value: Value,
+    ) -> Result<(), InterpretError> {
+        match (target, index) {
+            (Value::List(list), Value::Int(i)) => {
+                let len = list.borrow().len();
+                let i = self.list_index(*i, len)?;
+                list.borrow_mut()[i] = value;
+            }
+            (Value::Map(map), Value::String(key)) => {
+                map.borrow_mut().insert(key.clone(), value);
+            }
+            (target, index) => {
+                let msg = format!(
+                    "Cannot assign to {} with a {} index",
+                    target.type_name(),
+                    index.type_name()
+                );
+                return Err(self.runtime_error(&msg));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn list_index(&mut self, index: i64, len: usize) -> Result<usize, InterpretError> {
+        let i = if index < 0 { index + len as i64 } else { index };
+        if i < 0 || i >= len as i64 {
+            let msg = format!("Index {} out of range for length {}", index, len);
+            return Err(self.runtime_error(&msg));
+        }
+
+        Ok(i as usize)
+    }
+
+    /// reuses the upvalue already pointing at a stack slot so closures share it
+    fn capture_upvalue(&mut self, index: usize) -> Rc<RefCell<Upvalue>> {
+        for upvalue in &self.open_upvalues {
+            if let Upvalue::Open(i) = *upvalue.borrow() {
+                if i == index {
+                    return upvalue.clone();
+                }
+            }
+        }
+
+        let upvalue = Rc::new(RefCell::new(Upvalue::Open(index)));
+        self.open_upvalues.push(upvalue.clone());
+        upvalue
+    }
+
+    /// moves variables at or above `start` off the stack into their upvalues
+    fn close_upvalues(&mut self, start: usize) {
+        let stack = &self.stack;
+        self.open_upvalues.retain(|upvalue| {
+            let index = match *upvalue.borrow() {
+                Upvalue::Open(index) => index,
+                Upvalue::Closed(_) => return false,
+            };
+
+            if index < start {
+                return true;
+            }
+
+            *upvalue.borrow_mut() = Upvalue::Closed(stack[index].clone());
+            false
+        });
+    }
+
+    /// raises an error value with the message, catchable like a thrown value
     fn runtime_error(&mut self, msg: &str) -> InterpretError {
-        let ip = self.frames.last_mut().unwrap().ip;
-        let line = self.frames.last_mut().unwrap().function.chunk.lines[ip - 1];
-        println!("{} [line {}]", msg, line);
+        let error = self.error_value(msg);
+        self.throw(error)
+    }
+
+    fn throw(&mut self, value: Value) -> InterpretError {
+        self.thrown = Some((value, self.current_line()));
         InterpretError::RuntimeError
     }
 
+    /// unwinds to the innermost handler and jumps to its catch with the thrown
+    /// value on the stack. Reports the error if nothing catches it
+    fn catch_thrown(&mut self) -> bool {
+        let (value, line) = self.thrown.take().unwrap();
+
+        while let Some(frame) = self.frames.last_mut() {
+            if let Some(handler) = frame.handlers.pop() {
+                frame.ip = handler.catch_ip;
+                self.close_upvalues(handler.stack_len);
+                self.stack.truncate(handler.stack_len);
+                self.push(value);
+                return true;
+            }
+
+            let frame = self.frames.pop().unwrap();
+            self.close_upvalues(frame.base);
+            self.stack.truncate(frame.base);
+            if frame.exports.is_some() {
+                self.loading.pop();
+            }
+        }
+
+        let message = match &value {
+            Value::Map(map) => match map.borrow().get("message") {
+                Some(Value::String(msg)) => Some(msg.clone()),
+                _ => None,
+            },
+            _ => None,
+        };
+        match message {
+            Some(msg) => println!("{} [line {}]", msg, line),
+            None => println!("Uncaught {} [line {}]", value.repr(), line),
+        }
+        false
+    }
+
+    /// an error is a map holding its message, the line it happened on and a
+    /// trace of the calls leading to it, innermost first
+    pub fn error_value(&self, msg: &str) -> Value {
+        let trace = self
+            .frames
+            .iter()
+            .rev()
+            .map(|frame| {
+                let line = frame.function.chunk.lines[frame.ip.saturating_sub(1)];
+                let name = match frame.function.function_type {
+                    FunctionType::Script if frame.function.name.is_empty() => {
+                        String::from("script")
+                    }
+                    FunctionType::Script => format!("module {}", frame.function.name),
+                    FunctionType::Fn if frame.function.name.is_empty() => String::from("<fn>"),
+                    FunctionType::Fn => format!("{}()", frame.function.name),
+                };
+                Value::String(format!("line {} in {}", line, name))
+            })
+            .collect::<Vec<_>>();
+
+        let mut error = BTreeMap::new();
+        error.insert(String::from("message"), Value::String(String::from(msg)));
+        error.insert(String::from("line"), Value::Int(self.current_line() as i64));
+        error.insert(String::from("trace"), Value::from(trace));
+        Value::from(error)
+    }
+
+    fn current_line(&self) -> usize {
+        let frame = self.frames.last().unwrap();
+        frame.function.chunk.lines[frame.ip.saturating_sub(1)]
+    }
+
     fn read_byte(&mut self) -> u8 {
         let ip = self.frames.last_mut().unwrap().ip;
         let byte = self.frames.last_mut().unwrap().function.chunk.code[ip];
@@ -212,6 +937,13 @@ impl Vm {
         self.frames.last_mut().unwrap().function.chunk.constants[byte as usize].clone()
     }
 
+    fn read_string(&mut self) -> String {
+        match self.read_constant() {
+            Value::String(s) => s,
+            _ => unreachable!("Expected a String constant"),
+        }
+    }
+
     fn push(&mut self, value: Value) {
         self.stack.push(value)
     }
@@ -225,135 +957,303 @@ impl Vm {
         &self.stack[len - 1 - offset]
     }
 
-    fn binary_op(&mut self, op: Operator) {
+    fn binary_op(&mut self, op: Operator) -> Result<(), InterpretError> {
         let val2 = self.pop();
         let val1 = self.pop();
 
-        match (val1, val2) {
-            (Value::Number(a), Value::Number(b)) => {
-                let result = match op {
-                    Operator::Plus => Value::Number(a + b),
-                    Operator::Minus => Value::Number(a - b),
-                    Operator::Star => Value::Number(a * b),
-                    Operator::Slash => Value::Number(a / b),
-                    Operator::Mod => Value::Number(a % b),
-                    Operator::GreaterThan => Value::Bool(a > b),
-                    Operator::LessThan => Value::Bool(a < b),
-                    Operator::Amp => {
-                        let a_diff = (a - a.round()).abs();
-                        let b_diff = (b - b.round()).abs();
-
-                        if a_diff > 0f64 || b_diff > 0f64 {
-                            self.runtime_error("Cannot use fp operands for & operator");
-                        }
+        let result = match (val1, val2) {
+            (Value::Int(a), Value::Int(b)) => self.int_op(op, a, b)?,
+            (Value::Number(a), Value::Number(b)) => self.float_op(op, a, b)?,
+            // ordering an int and a float is exact, like `==`
+            (a @ (Value::Int(_) | Value::BigInt(_)), b @ Value::Number(_))
+            | (a @ Value::Number(_), b @ (Value::Int(_) | Value::BigInt(_)))
+                if matches!(op, Operator::GreaterThan | Operator::LessThan) =>
+            {
+                let expected = match op {
+                    Operator::GreaterThan => Ordering::Greater,
+                    _ => Ordering::Less,
+                };
+                Value::Bool(compare(&a, &b) == Some(expected))
+            }
+            // mixing an int with a float promotes the int to a float
+            (Value::Int(a), Value::Number(b)) => self.float_op(op, a as f64, b)?,
+            (Value::Number(a), Value::Int(b)) => self.float_op(op, a, b as f64)?,
+            (Value::BigInt(a), Value::BigInt(b)) => self.big_op(op, a, b)?,
+            (Value::BigInt(a), Value::Int(b)) => self.big_op(op, a, BigInt::from(b))?,
+            (Value::Int(a), Value::BigInt(b)) => self.big_op(op, BigInt::from(a), b)?,
+            (Value::BigInt(a), Value::Number(b)) => self.float_op(op, a.to_f64(), b)?,
+            (Value::Number(a), Value::BigInt(b)) => self.float_op(op, a, b.to_f64())?,
+            (a @ Value::Bool(_), b) | (a, b @ Value::Bool(_)) => self.bool_op(op, a, b)?,
+            (Value::String(a), Value::String(b)) => match op {
+                Operator::Plus => Value::String(format!("{}{}", a, b)),
+                Operator::AmpAmp => Value::Bool(!a.is_empty() && !b.is_empty()),
+                Operator::PipePipe => Value::Bool(!a.is_empty() || !b.is_empty()),
+                _ => {
+                    let msg = format!("no {} operation on string '{}' and '{}'", op, a, b);
+                    return Err(self.runtime_error(&msg));
+                }
+            },
+            (a, b) => {
+                let msg = format!("no {} operation on {} and {}", op, a, b);
+                return Err(self.runtime_error(&msg));
+            }
+        };
 
-                        Value::Number((a.round() as i64 & b.round() as i64) as f64)
-                    }
-                    Operator::Pipe => {
-                        let a_diff = (a - a.round()).abs();
-                        let b_diff = (b - b.round()).abs();
+        self.push(result);
+        Ok(())
+    }
 
-                        if a_diff > 0f64 || b_diff > 0f64 {
-                            self.runtime_error("Cannot use fp operands for | operator");
-                        }
+    /// integer arithmetic, promoting to a bigint when the result overflows an i64
+    fn int_op(&mut self, op: Operator, a: i64, b: i64) -> Result<Value, InterpretError> {
+        let result = match op {
+            Operator::Plus => a.checked_add(b),
+            Operator::Minus => a.checked_sub(b),
+            Operator::Star => a.checked_mul(b),
+            // `/` is always true division
+            Operator::Slash => return self.float_op(op, a as f64, b as f64),
+            Operator::SlashSlash => {
+                if b == 0 {
+                    return Err(self.runtime_error("Integer division by zero"));
+                }
+
+                // round the quotient towards negative infinity
+                a.checked_div(b).map(|q| {
+                    if a % b != 0 && (a < 0) != (b < 0) {
+                        q - 1
+                    } else {
+                        q
+                    }
+                })
+            }
+            Operator::Mod => {
+                if b == 0 {
+                    return Err(self.runtime_error("Integer division by zero"));
+                }
 
-                        Value::Number((a.round() as i64 | b.round() as i64) as f64)
+                // the remainder takes the sign of the divisor, like `//`
+                a.checked_rem(b).map(|r| {
+                    if r != 0 && (r < 0) != (b < 0) {
+                        r + b
+                    } else {
+                        r
                     }
-                    Operator::AmpAmp => Value::Bool(a != 0f64 && b != 0f64),
-                    Operator::PipePipe => Value::Bool(a != 0f64 || b != 0f64),
-                };
+                })
+            }
+            Operator::GreaterThan => return Ok(Value::Bool(a > b)),
+            Operator::LessThan => return Ok(Value::Bool(a < b)),
+            Operator::Amp => Some(a & b),
+            Operator::Pipe => Some(a | b),
+            Operator::Caret => Some(a ^ b),
+            Operator::LessLess => {
+                if b < 0 {
+                    return Err(self.runtime_error("Negative shift amount"));
+                }
 
-                self.push(result)
+                if a == 0 {
+                    Some(0)
+                } else if b >= 64 || (a << b) >> b != a {
+                    None
+                } else {
+                    Some(a << b)
+                }
             }
-            (Value::Bool(n), Value::Number(m)) => {
-                let (a, b) = (1f64, m);
-
-                let result = match op {
-                    Operator::Plus
-                    | Operator::Minus
-                    | Operator::Star
-                    | Operator::Slash
-                    | Operator::Mod
-                    | Operator::GreaterThan
-                    | Operator::LessThan => {
-                        self.runtime_error("operands must be numbers");
-                        Value::Nil
-                    }
-                    Operator::Amp => Value::Number((a as i64 & b.round() as i64) as f64),
-                    Operator::Pipe => Value::Number((a as i64 | b.round() as i64) as f64),
-                    Operator::AmpAmp => Value::Bool(n && b != 0f64),
-                    Operator::PipePipe => Value::Bool(n || b != 0f64),
-                };
+            Operator::GreaterGreater => {
+                if b < 0 {
+                    return Err(self.runtime_error("Negative shift amount"));
+                }
 
-                self.push(result)
+                Some(a >> b.min(63))
             }
-            (Value::Number(n), Value::Bool(m)) => {
-                let (a, b) = (n, 1f64);
-
-                let result = match op {
-                    Operator::Plus
-                    | Operator::Minus
-                    | Operator::Star
-                    | Operator::Slash
-                    | Operator::Mod
-                    | Operator::GreaterThan
-                    | Operator::LessThan => {
-                        self.runtime_error("operands must be numbers");
-                        Value::Nil
-                    }
-                    Operator::Amp => Value::Number((a.round() as i64 & b as i64) as f64),
-                    Operator::Pipe => Value::Number((a.round() as i64 | b as i64) as f64),
-                    Operator::AmpAmp => Value::Bool(a != 0f64 && m),
-                    Operator::PipePipe => Value::Bool(a != 0f64 || m),
+            Operator::StarStar => {
+                // a negative exponent gives a fraction
+                if b < 0 {
+                    return self.float_op(op, a as f64, b as f64);
+                }
+
+                u32::try_from(b).ok().and_then(|b| a.checked_pow(b))
+            }
+            Operator::AmpAmp => return Ok(Value::Bool(a != 0 && b != 0)),
+            Operator::PipePipe => return Ok(Value::Bool(a != 0 || b != 0)),
+        };
+
+        match result {
+            Some(n) => Ok(Value::Int(n)),
+            None => self.big_op(op, BigInt::from(a), BigInt::from(b)),
+        }
+    }
+
+    fn big_op(&mut self, op: Operator, a: BigInt, b: BigInt) -> Result<Value, InterpretError> {
+        let result = match op {
+            Operator::Plus => &a + &b,
+            Operator::Minus => &a - &b,
+            Operator::Star => &a * &b,
+            Operator::Slash => return self.float_op(op, a.to_f64(), b.to_f64()),
+            Operator::SlashSlash | Operator::Mod => {
+                let result = if let Operator::SlashSlash = op {
+                    a.checked_div_floor(&b)
+                } else {
+                    a.checked_rem_floor(&b)
                 };
 
-                self.push(result)
+                match result {
+                    Some(n) => n,
+                    None => return Err(self.runtime_error("Integer division by zero")),
+                }
             }
-            (Value::Bool(n), Value::Bool(m)) => {
-                let (a, b) = (1f64, 1f64);
-
-                let result = match op {
-                    Operator::Plus
-                    | Operator::Minus
-                    | Operator::Star
-                    | Operator::Slash
-                    | Operator::Mod
-                    | Operator::GreaterThan
-                    | Operator::LessThan => {
-                        self.runtime_error("operands must be numbers");
-                        Value::Nil
+            Operator::GreaterThan => return Ok(Value::Bool(a > b)),
+            Operator::LessThan => return Ok(Value::Bool(a < b)),
+            Operator::Amp => &a & &b,
+            Operator::Pipe => &a | &b,
+            Operator::Caret => &a ^ &b,
+            Operator::LessLess | Operator::GreaterGreater => {
+                let shift = match b.to_i64() {
+                    Some(n) if n < 0 => {
+                        return Err(self.runtime_error("Negative shift amount"));
+                    }
+                    Some(n) if n <= MAX_SHIFT => n as usize,
+                    // shifting right by a huge amount leaves only the sign
+                    _ if matches!(op, Operator::GreaterGreater) => {
+                        return Ok(Value::Int(if a.is_negative() { -1 } else { 0 }));
                     }
-                    Operator::Amp => Value::Number((a as i64 & b as i64) as f64),
-                    Operator::Pipe => Value::Number((a as i64 | b as i64) as f64),
-                    Operator::AmpAmp => Value::Bool(n && m),
-                    Operator::PipePipe => Value::Bool(n || m),
+                    _ => return Err(self.runtime_error("Shift amount is too large")),
                 };
 
-                self.push(result)
+                if let Operator::LessLess = op {
+                    &a << shift
+                } else {
+                    &a >> shift
+                }
             }
-            (Value::String(a), Value::String(b)) => {
-                let result: Value = match op {
-                    Operator::Plus => Value::String(format!("{}{}", a, b)),
-                    Operator::Minus
-                    | Operator::Star
-                    | Operator::Slash
-                    | Operator::Mod
-                    | Operator::GreaterThan
-                    | Operator::LessThan => {
-                        let msg = format!("no {} operation on string '{}' and '{}'", op, a, b);
-                        self.runtime_error(&msg);
-                        Value::Nil
+            Operator::StarStar => {
+                if b.is_negative() {
+                    return self.float_op(op, a.to_f64(), b.to_f64());
+                }
+
+                // 0, 1 and -1 stay small whatever the exponent
+                let odd = b
+                    .checked_rem(&BigInt::from(2))
+                    .is_some_and(|r| !r.is_zero());
+                match (a.to_i64(), b.to_i64()) {
+                    (Some(0), _) => BigInt::from(b.is_zero() as i64),
+                    (Some(1), _) => BigInt::from(1),
+                    (Some(-1), _) => BigInt::from(if odd { -1 } else { 1 }),
+                    (_, Some(n)) if (a.bits() - 1).saturating_mul(n as u64) <= MAX_SHIFT as u64 => {
+                        a.pow(n as u64)
                     }
-                    Operator::AmpAmp => Value::Bool(!a.is_empty() && !b.is_empty()),
-                    Operator::PipePipe => Value::Bool(!a.is_empty() && !b.is_empty()),
-                    _ => unreachable!("binary_op: invalid op {}", op),
-                };
+                    _ => return Err(self.runtime_error("Exponent is too large")),
+                }
+            }
+            Operator::AmpAmp => return Ok(Value::Bool(!a.is_zero() && !b.is_zero())),
+            Operator::PipePipe => return Ok(Value::Bool(!a.is_zero() || !b.is_zero())),
+        };
 
-                self.push(result)
+        Ok(Value::from(result))
+    }
+
+    fn float_op(&mut self, op: Operator, a: f64, b: f64) -> Result<Value, InterpretError> {
+        let result = match op {
+            Operator::Plus => Value::Number(a + b),
+            Operator::Minus => Value::Number(a - b),
+            Operator::Star => Value::Number(a * b),
+            Operator::Slash => Value::Number(a / b),
+            Operator::SlashSlash => Value::Number((a / b).floor()),
+            Operator::Mod => {
+                let r = a % b;
+                if r != 0f64 && (r < 0f64) != (b < 0f64) {
+                    Value::Number(r + b)
+                } else {
+                    Value::Number(r)
+                }
             }
-            _ => {
-                unreachable!("binary_op: invalid op {}", op);
+            Operator::StarStar => Value::Number(a.powf(b)),
+            Operator::GreaterThan => Value::Bool(a > b),
+            Operator::LessThan => Value::Bool(a < b),
+            Operator::AmpAmp => Value::Bool(a != 0f64 && b != 0f64),
+            Operator::PipePipe => Value::Bool(a != 0f64 || b != 0f64),
+            Operator::Amp
+            | Operator::Pipe
+            | Operator::Caret
+            | Operator::LessLess
+            | Operator::GreaterGreater => {
+                let msg = format!("Cannot use fp operands for {} operator", op);
+                return Err(self.runtime_error(&msg));
             }
-        }
+        };
+
+        Ok(result)
+    }
+
+    /// booleans act as 0 or 1 in bitwise operations and can't be used in arithmetic
+    fn bool_op(&mut self, op: Operator, a: Value, b: Value) -> Result<Value, InterpretError> {
+        let as_int = |v: &Value| match v {
+            Value::Bool(x) => Some(*x as i64),
+            Value::Int(x) => Some(*x),
+            _ => None,
+        };
+        let truthy = |v: &Value| match v {
+            Value::Int(x) => *x != 0,
+            Value::Number(x) => *x != 0f64,
+            Value::String(s) => !s.is_empty(),
+            _ => !v.is_falsey(),
+        };
+
+        let result = match op {
+            Operator::AmpAmp => Value::Bool(truthy(&a) && truthy(&b)),
+            Operator::PipePipe => Value::Bool(truthy(&a) || truthy(&b)),
+            Operator::Amp | Operator::Pipe | Operator::Caret => match (as_int(&a), as_int(&b)) {
+                (Some(x), Some(y)) => match op {
+                    Operator::Amp => Value::Int(x & y),
+                    Operator::Pipe => Value::Int(x | y),
+                    _ => Value::Int(x ^ y),
+                },
+                _ => {
+                    let msg = format!("Operands of {} must be integers or booleans", op);
+                    return Err(self.runtime_error(&msg));
+                }
+            },
+            _ => return Err(self.runtime_error("operands must be numbers")),
+        };
+
+        Ok(result)
+    }
+}
+
+/// orders two numbers or two strings, other values can't be compared
+pub fn compare(a: &Value, b: &Value) -> Option<Ordering> {
+    match (a, b) {
+        (Value::Int(a), Value::Int(b)) => Some(a.cmp(b)),
+        (Value::BigInt(a), Value::BigInt(b)) => Some(a.cmp(b)),
+        (Value::BigInt(a), Value::Int(b)) => Some(a.cmp(&BigInt::from(*b))),
+        (Value::Int(a), Value::BigInt(b)) => Some(BigInt::from(*a).cmp(b)),
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        (Value::Number(a), Value::Number(b)) => a.partial_cmp(b),
+        (Value::Number(x), n) => Some(compare_int_float(n, *x)?.reverse()),
+        (n, Value::Number(x)) => compare_int_float(n, *x),
+        _ => None,
+    }
+}
+
+/// orders an int against a float without rounding the int to a float
+fn compare_int_float(n: &Value, x: f64) -> Option<Ordering> {
+    let n = match n {
+        Value::Int(n) => BigInt::from(*n),
+        Value::BigInt(n) => n.clone(),
+        _ => return None,
+    };
+
+    if x.is_nan() {
+        return None;
+    } else if x.is_infinite() {
+        return Some(if x > 0f64 {
+            Ordering::Less
+        } else {
+            Ordering::Greater
+        });
+    }
+
+    // an int equal to the float's floor is still below it if it has a fraction
+    match n.cmp(&BigInt::from_f64(x.floor())?) {
+        Ordering::Equal if x.fract() != 0f64 => Some(Ordering::Less),
+        ordering => Some(ordering),
     }
 }